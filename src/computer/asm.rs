@@ -0,0 +1,382 @@
+use crate::computer::instruction::{encode, Instruction};
+use crate::computer::PROGRAM_START_ADDR;
+
+// Operand of an assembled instruction, see `chip8_operand!`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    V(u8),
+    I,
+    // `[i]` of `ld [i], vx` and `ld vx, [i]`
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Number(u16),
+    Label(&'static str),
+}
+
+// Line of a `chip8!` program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Label(&'static str),
+    Instruction(&'static str, &'static [Operand]),
+}
+
+// Assembler for CHIP-8 mnemonics in Cowgod's notation. The functions are const and
+// `chip8!` evaluates them in constants, so invalid instructions, operands out of range
+// and undefined labels are compile errors. Const panics can't format, the messages
+// don't name the offending line, the error points at the `chip8!` invocation instead.
+
+// Number of ROM bytes the program assembles to
+pub const fn size(program: &[Line]) -> usize {
+    let mut size = 0;
+    let mut index = 0;
+    while index < program.len() {
+        size += line_size(&program[index]);
+        index += 1;
+    }
+    size
+}
+
+// Assembles the program, `N` is its `size`
+pub const fn assemble<const N: usize>(program: &[Line]) -> [u8; N] {
+    let mut rom = [0; N];
+    let mut offset = 0;
+    let mut index = 0;
+    while index < program.len() {
+        match &program[index] {
+            Line::Label(name) => {
+                if label_count(program, name) > 1 {
+                    panic!("chip8!: label defined twice");
+                }
+            },
+            Line::Instruction(mnemonic, operands) if same(mnemonic, "db") => {
+                let mut operand = 0;
+                while operand < operands.len() {
+                    rom[offset] = number(&operands[operand], 0xFF) as u8;
+                    offset += 1;
+                    operand += 1;
+                }
+            },
+            Line::Instruction(mnemonic, operands) if same(mnemonic, "dw") => {
+                let mut operand = 0;
+                while operand < operands.len() {
+                    let word = number(&operands[operand], 0xFFFF);
+                    rom[offset] = (word >> 8) as u8;
+                    rom[offset + 1] = word as u8;
+                    offset += 2;
+                    operand += 1;
+                }
+            },
+            Line::Instruction(mnemonic, operands) => {
+                let opcode = encode(&instruction(program, mnemonic, operands)).value();
+                rom[offset] = (opcode >> 8) as u8;
+                rom[offset + 1] = opcode as u8;
+                offset += 2;
+            },
+        }
+        index += 1;
+    }
+    rom
+}
+
+const fn instruction(program: &[Line], mnemonic: &str, operands: &[Operand]) -> Instruction {
+    use Operand::*;
+
+    match operands {
+        [] if same(mnemonic, "cls") => Instruction::Cls,
+        [] if same(mnemonic, "ret") => Instruction::Ret,
+        [] if same(mnemonic, "low") => Instruction::Low,
+        [] if same(mnemonic, "high") => Instruction::High,
+        [V(0), addr] if same(mnemonic, "jp") => Instruction::JpV0(address(program, addr)),
+        [addr] if same(mnemonic, "jp") => Instruction::Jp(address(program, addr)),
+        [addr] if same(mnemonic, "call") => Instruction::Call(address(program, addr)),
+        [V(x), V(y)] if same(mnemonic, "se") => Instruction::SeReg(*x, *y),
+        [V(x), byte] if same(mnemonic, "se") => Instruction::SeByte(*x, number(byte, 0xFF) as u8),
+        [V(x), V(y)] if same(mnemonic, "sne") => Instruction::SneReg(*x, *y),
+        [V(x), byte] if same(mnemonic, "sne") => Instruction::SneByte(*x, number(byte, 0xFF) as u8),
+        [V(x), V(y)] if same(mnemonic, "ld") => Instruction::LdReg(*x, *y),
+        [V(x), Dt] if same(mnemonic, "ld") => Instruction::LdRegDt(*x),
+        [V(x), K] if same(mnemonic, "ld") => Instruction::LdRegK(*x),
+        [V(x), IndirectI] if same(mnemonic, "ld") => Instruction::LdRegsMem(*x),
+        [V(x), byte] if same(mnemonic, "ld") => Instruction::LdByte(*x, number(byte, 0xFF) as u8),
+        [I, addr] if same(mnemonic, "ld") => Instruction::LdI(address(program, addr)),
+        [Dt, V(x)] if same(mnemonic, "ld") => Instruction::LdDtReg(*x),
+        [St, V(x)] if same(mnemonic, "ld") => Instruction::LdStReg(*x),
+        [F, V(x)] if same(mnemonic, "ld") => Instruction::LdFReg(*x),
+        [B, V(x)] if same(mnemonic, "ld") => Instruction::LdBReg(*x),
+        [IndirectI, V(x)] if same(mnemonic, "ld") => Instruction::LdMemRegs(*x),
+        [I, V(x)] if same(mnemonic, "add") => Instruction::AddIReg(*x),
+        [V(x), V(y)] if same(mnemonic, "add") => Instruction::AddReg(*x, *y),
+        [V(x), byte] if same(mnemonic, "add") => Instruction::AddByte(*x, number(byte, 0xFF) as u8),
+        [V(x), V(y)] if same(mnemonic, "or") => Instruction::Or(*x, *y),
+        [V(x), V(y)] if same(mnemonic, "and") => Instruction::And(*x, *y),
+        [V(x), V(y)] if same(mnemonic, "xor") => Instruction::Xor(*x, *y),
+        [V(x), V(y)] if same(mnemonic, "sub") => Instruction::Sub(*x, *y),
+        [V(x)] if same(mnemonic, "shr") => Instruction::Shr(*x, *x),
+        [V(x), V(y)] if same(mnemonic, "shr") => Instruction::Shr(*x, *y),
+        [V(x), V(y)] if same(mnemonic, "subn") => Instruction::Subn(*x, *y),
+        [V(x)] if same(mnemonic, "shl") => Instruction::Shl(*x, *x),
+        [V(x), V(y)] if same(mnemonic, "shl") => Instruction::Shl(*x, *y),
+        [V(x), byte] if same(mnemonic, "rnd") => Instruction::Rnd(*x, number(byte, 0xFF) as u8),
+        [V(x), V(y), height] if same(mnemonic, "drw") => Instruction::Drw(*x, *y, number(height, 0xF) as u8),
+        [V(x)] if same(mnemonic, "skp") => Instruction::Skp(*x),
+        [V(x)] if same(mnemonic, "sknp") => Instruction::Sknp(*x),
+        _ => panic!("chip8!: invalid instruction or operands"),
+    }
+}
+
+const fn line_size(line: &Line) -> usize {
+    match line {
+        Line::Label(_) => 0,
+        Line::Instruction(mnemonic, operands) if same(mnemonic, "db") => operands.len(),
+        Line::Instruction(mnemonic, operands) if same(mnemonic, "dw") => operands.len() * 2,
+        Line::Instruction(..) => 2,
+    }
+}
+
+// `nnn` operand, a number or a label defined anywhere in the program
+const fn address(program: &[Line], operand: &Operand) -> u16 {
+    let name = match operand {
+        Operand::Label(name) => name,
+        _ => return number(operand, 0xFFF),
+    };
+
+    let mut addr = PROGRAM_START_ADDR;
+    let mut index = 0;
+    while index < program.len() {
+        if let Line::Label(label) = &program[index] {
+            if same(label, name) {
+                return addr as u16;
+            }
+        }
+        addr += line_size(&program[index]);
+        index += 1;
+    }
+    // also typos of registers, e.g. `vg`, end up here
+    panic!("chip8!: undefined label")
+}
+
+const fn label_count(program: &[Line], name: &str) -> usize {
+    let mut count = 0;
+    let mut index = 0;
+    while index < program.len() {
+        if let Line::Label(label) = &program[index] {
+            if same(label, name) {
+                count += 1;
+            }
+        }
+        index += 1;
+    }
+    count
+}
+
+const fn number(operand: &Operand, max: u16) -> u16 {
+    match operand {
+        Operand::Number(value) if *value <= max => *value,
+        Operand::Number(_) => panic!("chip8!: number out of range"),
+        // labels are only addresses of `jp`, `call` and `ld i`, so a register typo is caught too
+        _ => panic!("chip8!: expected a number"),
+    }
+}
+
+// `==` on strings isn't const
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut index = 0;
+    while index < a.len() {
+        if a[index] != b[index] {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
+// Assembles a program into ROM bytes for `Computer::load_rom`, e.g.
+// `chip8! { ld v0, 5; loop: add v0, 1; jp loop }`
+// The program is assembled in constants, errors in it fail the build.
+#[macro_export]
+macro_rules! chip8 {
+    ($($body:tt)*) => {{
+        const PROGRAM: &[$crate::computer::asm::Line] = &$crate::chip8_lines!([]; $($body)*);
+        const SIZE: usize = $crate::computer::asm::size(PROGRAM);
+        const ROM: [u8; SIZE] = $crate::computer::asm::assemble(PROGRAM);
+        ROM.to_vec()
+    }};
+}
+
+// Collects program lines into an array expression
+#[macro_export]
+#[doc(hidden)]
+macro_rules! chip8_lines {
+    ([$($lines:expr),*];) => { [$($lines),*] };
+    ([$($lines:expr),*]; $label:ident : $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::computer::asm::Line::Label(stringify!($label))]; $($rest)*)
+    };
+    // common operand counts are matched directly to keep the recursion shallow
+    ([$($lines:expr),*]; $mnemonic:ident ; $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic;)]; $($rest)*)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident $x:tt ; $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic; $x)]; $($rest)*)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident $x:tt , $y:tt ; $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic; $x, $y)]; $($rest)*)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident $x:tt , $y:tt , $z:tt ; $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic; $x, $y, $z)]; $($rest)*)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident $($rest:tt)*) => {
+        $crate::chip8_instruction!([$($lines),*]; $mnemonic []; $($rest)*)
+    };
+}
+
+// Collects operand tokens up to the next `;`, used for long `db`/`dw` lists and the last line
+#[macro_export]
+#[doc(hidden)]
+macro_rules! chip8_instruction {
+    ([$($lines:expr),*]; $mnemonic:ident [$($operands:tt)*]; ; $($rest:tt)*) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic; $($operands)*)]; $($rest)*)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident [$($operands:tt)*];) => {
+        $crate::chip8_lines!([$($lines,)* $crate::chip8_line!($mnemonic; $($operands)*)];)
+    };
+    ([$($lines:expr),*]; $mnemonic:ident [$($operands:tt)*]; $next:tt $($rest:tt)*) => {
+        $crate::chip8_instruction!([$($lines),*]; $mnemonic [$($operands)* $next]; $($rest)*)
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! chip8_line {
+    ($mnemonic:ident; $($operand:tt),*) => {
+        $crate::computer::asm::Line::Instruction(stringify!($mnemonic), &[$($crate::chip8_operand!($operand)),*])
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! chip8_operand {
+    (v0) => { $crate::computer::asm::Operand::V(0x0) };
+    (v1) => { $crate::computer::asm::Operand::V(0x1) };
+    (v2) => { $crate::computer::asm::Operand::V(0x2) };
+    (v3) => { $crate::computer::asm::Operand::V(0x3) };
+    (v4) => { $crate::computer::asm::Operand::V(0x4) };
+    (v5) => { $crate::computer::asm::Operand::V(0x5) };
+    (v6) => { $crate::computer::asm::Operand::V(0x6) };
+    (v7) => { $crate::computer::asm::Operand::V(0x7) };
+    (v8) => { $crate::computer::asm::Operand::V(0x8) };
+    (v9) => { $crate::computer::asm::Operand::V(0x9) };
+    (va) => { $crate::computer::asm::Operand::V(0xA) };
+    (vb) => { $crate::computer::asm::Operand::V(0xB) };
+    (vc) => { $crate::computer::asm::Operand::V(0xC) };
+    (vd) => { $crate::computer::asm::Operand::V(0xD) };
+    (ve) => { $crate::computer::asm::Operand::V(0xE) };
+    (vf) => { $crate::computer::asm::Operand::V(0xF) };
+    (i) => { $crate::computer::asm::Operand::I };
+    ([i]) => { $crate::computer::asm::Operand::IndirectI };
+    (dt) => { $crate::computer::asm::Operand::Dt };
+    (st) => { $crate::computer::asm::Operand::St };
+    (k) => { $crate::computer::asm::Operand::K };
+    (f) => { $crate::computer::asm::Operand::F };
+    (b) => { $crate::computer::asm::Operand::B };
+    ($label:ident) => { $crate::computer::asm::Operand::Label(stringify!($label)) };
+    ($value:expr) => { $crate::computer::asm::Operand::Number($value) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(bytes: &[u8]) -> Vec<u16> {
+        bytes.chunks(2).map(|word| u16::from_be_bytes([word[0], word[1]])).collect()
+    }
+
+    #[test]
+    fn assembles_labels_forward_and_backward() {
+        let rom = crate::chip8! {
+            ld v0, 5;
+            loop: add v0, 1;
+            se v0, 0x10;
+            jp loop;
+            call end;
+            end: ret
+        };
+        assert_eq!(words(&rom), [0x6005, 0x7001, 0x3010, 0x1202, 0x220A, 0x00EE]);
+    }
+
+    #[test]
+    fn assembles_every_instruction() {
+        let rom = crate::chip8! {
+            cls; ret; low; high; jp 0x300; jp v0, 0x300; call 0x300;
+            se v1, 2; se v1, v2; sne v1, 2; sne v1, v2;
+            ld v1, 2; ld v1, v2; ld i, 0x300; ld v1, dt; ld v1, k; ld dt, v1; ld st, v1;
+            ld f, v1; ld b, v1; ld [i], v1; ld v1, [i];
+            add v1, 2; add v1, v2; add i, v1;
+            or v1, v2; and v1, v2; xor v1, v2; sub v1, v2; shr v1, v2; subn v1, v2; shl v1; shl v1, v2;
+            rnd v1, 0xff; drw v1, v2, 15; skp v1; sknp vf;
+        };
+        assert_eq!(
+            words(&rom),
+            [
+                0x00E0, 0x00EE, 0x00FE, 0x00FF, 0x1300, 0xB300, 0x2300,
+                0x3102, 0x5120, 0x4102, 0x9120,
+                0x6102, 0x8120, 0xA300, 0xF107, 0xF10A, 0xF115, 0xF118,
+                0xF129, 0xF133, 0xF155, 0xF165,
+                0x7102, 0x8124, 0xF11E,
+                0x8121, 0x8122, 0x8123, 0x8125, 0x8126, 0x8127, 0x811E, 0x812E,
+                0xC1FF, 0xD12F, 0xE19E, 0xEFA1,
+            ]
+        );
+    }
+
+    #[test]
+    fn assembles_data() {
+        let rom = crate::chip8! { ld i, sprite; sprite: db 0xff, 0x81; dw 0x1234 };
+        assert_eq!(rom, [0xA2, 0x02, 0xFF, 0x81, 0x12, 0x34]);
+    }
+
+    // `chip8!` runs the same checks in constants, where they fail the build
+
+    #[test]
+    #[should_panic(expected = "undefined label")]
+    fn rejects_undefined_labels() {
+        assemble::<2>(&[Line::Instruction("jp", &[Operand::Label("nowhere")])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "label defined twice")]
+    fn rejects_labels_defined_twice() {
+        assemble::<2>(&[Line::Label("end"), Line::Label("end"), Line::Instruction("ret", &[])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid instruction or operands")]
+    fn rejects_register_typos() {
+        // `vg` is taken for a label, which `ld` doesn't accept
+        assemble::<2>(&[Line::Instruction("ld", &[Operand::Label("vg"), Operand::Number(1)])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "number out of range")]
+    fn rejects_numbers_out_of_range() {
+        assemble::<2>(&[Line::Instruction("ld", &[Operand::V(0), Operand::Number(0x100)])]);
+    }
+
+    #[test]
+    fn program_size_counts_data() {
+        let program = [
+            Line::Label("start"),
+            Line::Instruction("cls", &[]),
+            Line::Instruction("db", &[Operand::Number(1), Operand::Number(2), Operand::Number(3)]),
+            Line::Instruction("dw", &[Operand::Number(0x1234)]),
+        ];
+        assert_eq!(size(&program), 7);
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::computer::opcode::Opcode;
+
+pub const MEMORY_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+// Single memory access made by the CPU
+#[derive(Debug, Clone, Copy)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub addr: u16,
+    // Value before the access
+    pub old_value: u8,
+    // Value after the access (same as old_value for reads and fetches)
+    pub new_value: u8,
+    // Address of the instruction that made the access
+    pub pc: u16,
+    pub opcode: u16,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:#05x}: {:#04x} -> {:#04x} (PC {:#05x}, opcode {:#06x})",
+            self.kind, self.addr, self.old_value, self.new_value, self.pc, self.opcode
+        )
+    }
+}
+
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Watchpoint {
+    pub fn new(range: RangeInclusive<u16>, read: bool, write: bool, execute: bool) -> Watchpoint {
+        Watchpoint { range, read, write, execute }
+    }
+
+    // Parses `rwx:ADDR` or `rwx:START-END`, e.g. `w:0x300-0x30f`
+    pub fn parse(value: &str) -> Result<Watchpoint, String> {
+        let (kinds, range) = value
+            .split_once(':')
+            .ok_or(format!("Invalid watchpoint: {value}"))?;
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+            None => {
+                let addr = parse_addr(range)?;
+                (addr, addr)
+            }
+        };
+        // a reversed range would never match
+        if start > end {
+            return Err(format!("Invalid watchpoint range: {range}"));
+        }
+
+        if kinds.is_empty() || kinds.chars().any(|c| !"rwx".contains(c)) {
+            return Err(format!("Invalid watchpoint access kinds: {kinds}"));
+        }
+
+        Ok(Watchpoint::new(
+            start..=end,
+            kinds.contains('r'),
+            kinds.contains('w'),
+            kinds.contains('x'),
+        ))
+    }
+
+    pub fn matches(&self, access: &Access) -> bool {
+        let kind_matches = match access.kind {
+            AccessKind::Read => self.read,
+            AccessKind::Write => self.write,
+            AccessKind::Execute => self.execute,
+        };
+
+        kind_matches && self.range.contains(&access.addr)
+    }
+}
+
+pub fn parse_addr(value: &str) -> Result<u16, String> {
+    let value = value.trim();
+    let parsed = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse::<u16>(),
+    };
+
+    parsed.map_err(|_| format!("Invalid address: {value}"))
+}
+
+pub type WatchHook = Box<dyn FnMut(&Access)>;
+
+// All CPU memory accesses go through the bus, so they can be watched
+pub struct Bus {
+    // 4KB RAM
+    pub memory: [u8; MEMORY_SIZE],
+    pub watchpoints: Vec<Watchpoint>,
+    // Keep the accesses of the current instruction, only set while a consumer needs them
+    pub record_accesses: bool,
+    // Accesses made by the current instruction
+    accesses: Vec<Access>,
+    // Accesses which matched a watchpoint and weren't handled yet
+    watch_hits: Vec<Access>,
+    // Callbacks fired on every watchpoint hit
+    watch_hooks: Vec<WatchHook>,
+    // Instruction currently being executed
+    pc: u16,
+    opcode: u16,
+}
+
+impl Bus {
+    pub fn new() -> Bus {
+        Bus {
+            memory: [0; MEMORY_SIZE],
+            watchpoints: Vec::new(),
+            record_accesses: false,
+            accesses: Vec::new(),
+            watch_hits: Vec::new(),
+            watch_hooks: Vec::new(),
+            pc: 0,
+            opcode: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.memory.fill(0);
+        self.accesses.clear();
+        self.watch_hits.clear();
+        self.pc = 0;
+        self.opcode = 0;
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn add_watch_hook(&mut self, hook: impl FnMut(&Access) + 'static) {
+        self.watch_hooks.push(Box::new(hook));
+    }
+
+    pub fn take_watch_hits(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    // Reads opcode at PC and makes it the context of all following accesses
+    // Addresses wrap around the 4KB address space
+    pub fn fetch(&mut self, pc: usize) -> Opcode {
+        let pc = pc % MEMORY_SIZE;
+        let next_addr = (pc + 1) % MEMORY_SIZE;
+        let opcode = Opcode::from(self.memory[pc], self.memory[next_addr]);
+        self.pc = pc as u16;
+        self.opcode = opcode.value();
+        self.accesses.clear();
+
+        for addr in [pc, next_addr] {
+            let value = self.memory[addr];
+            self.notify(AccessKind::Execute, addr, value, value);
+        }
+
+        opcode
+    }
+
+    pub fn read(&mut self, addr: usize) -> u8 {
+        let addr = addr % MEMORY_SIZE;
+        let value = self.memory[addr];
+        self.notify(AccessKind::Read, addr, value, value);
+        value
+    }
+
+    pub fn write(&mut self, addr: usize, value: u8) {
+        let addr = addr % MEMORY_SIZE;
+        let old_value = self.memory[addr];
+        self.memory[addr] = value;
+        self.notify(AccessKind::Write, addr, old_value, value);
+    }
+
+    fn notify(&mut self, kind: AccessKind, addr: usize, old_value: u8, new_value: u8) {
+        let access = Access {
+            kind,
+            addr: addr as u16,
+            old_value,
+            new_value,
+            pc: self.pc,
+            opcode: self.opcode,
+        };
+
+        if self.record_accesses {
+            self.accesses.push(access);
+        }
+
+        if self.watchpoints.iter().any(|watchpoint| watchpoint.matches(&access)) {
+            for hook in self.watch_hooks.iter_mut() {
+                hook(&access);
+            }
+            self.watch_hits.push(access);
+        }
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_watchpoints() {
+        let watchpoint = Watchpoint::parse("rw:0x300-0x30f").unwrap();
+        assert_eq!(watchpoint.range, 0x300..=0x30f);
+        assert!(watchpoint.read && watchpoint.write && !watchpoint.execute);
+        assert_eq!(Watchpoint::parse("x:0x200").unwrap().range, 0x200..=0x200);
+
+        assert_eq!(Watchpoint::parse("rw:0x30f-0x300").err(), Some(String::from("Invalid watchpoint range: 0x30f-0x300")));
+        assert!(Watchpoint::parse("q:0x300").is_err());
+        assert!(Watchpoint::parse("0x300").is_err());
+    }
+
+    #[test]
+    fn records_accesses_only_when_asked() {
+        let mut bus = Bus::new();
+        bus.fetch(0x200);
+        bus.write(0x300, 1);
+        assert!(bus.accesses().is_empty());
+
+        bus.record_accesses = true;
+        bus.fetch(0x202);
+        bus.write(0x300, 2);
+        let kinds: Vec<AccessKind> = bus.accesses().iter().map(|access| access.kind).collect();
+        assert_eq!(kinds, [AccessKind::Execute, AccessKind::Execute, AccessKind::Write]);
+        assert_eq!((bus.accesses()[2].old_value, bus.accesses()[2].new_value), (1, 2));
+    }
+
+    #[test]
+    fn watchpoints_hit_without_recording() {
+        let mut bus = Bus::new();
+        bus.add_watchpoint(Watchpoint::parse("w:0x300").unwrap());
+        bus.write(0x300, 1);
+        bus.read(0x301);
+        assert_eq!(bus.take_watch_hits().len(), 1);
+        assert!(bus.accesses().is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::computer::PROGRAM_START_ADDR;
+use crate::computer::bus::{parse_addr, Access, AccessKind, MEMORY_SIZE};
+use crate::computer::disasm::disassemble;
+use crate::computer::opcode::Opcode;
+
+// Tracks how each ROM byte was used: executed, read as data or never touched
+pub struct Coverage {
+    // Executions per address
+    executed: Vec<u32>,
+    // Data reads per address (I-relative loads and sprites)
+    read: Vec<u32>,
+    rom_end: usize,
+}
+
+// Source locations of ROM addresses, loaded from a line-map file
+pub struct LineMap {
+    // Address -> (source file, line number)
+    lines: BTreeMap<u16, (String, u32)>,
+}
+
+impl LineMap {
+    // Line-map files have one `ADDR FILE:LINE` entry per line, `#` starts a comment
+    pub fn load(path: &str) -> Result<LineMap, String> {
+        let data = fs::read_to_string(path).map_err(|e| format!("Unable to read line map {path}: {e}"))?;
+        LineMap::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> Result<LineMap, String> {
+        let mut lines = BTreeMap::new();
+
+        for line in data.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || format!("Invalid line map entry: {line}");
+            let (addr, location) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (file, line_number) = location.trim().rsplit_once(':').ok_or_else(invalid)?;
+            let line_number = line_number.parse::<u32>().map_err(|_| invalid())?;
+
+            lines.insert(parse_addr(addr)?, (file.to_string(), line_number));
+        }
+
+        Ok(LineMap { lines })
+    }
+}
+
+impl Coverage {
+    pub fn new(rom_size: usize) -> Coverage {
+        Coverage {
+            executed: vec![0; MEMORY_SIZE],
+            read: vec![0; MEMORY_SIZE],
+            rom_end: (PROGRAM_START_ADDR + rom_size).min(MEMORY_SIZE),
+        }
+    }
+
+    pub fn record(&mut self, accesses: &[Access]) {
+        for access in accesses {
+            let addr = access.addr as usize;
+            match access.kind {
+                AccessKind::Execute => self.executed[addr] += 1,
+                AccessKind::Read => self.read[addr] += 1,
+                AccessKind::Write => {},
+            }
+        }
+    }
+
+    // ROM disassembly with each byte marked as executed (X), data (D) or untouched (.)
+    pub fn annotated_disassembly(&self, memory: &[u8]) -> String {
+        let rom_size = self.rom_end - PROGRAM_START_ADDR;
+        let rom = PROGRAM_START_ADDR..self.rom_end;
+        let executed = rom.clone().filter(|addr| self.executed[*addr] > 0).count();
+        let data = rom.clone().filter(|addr| self.executed[*addr] == 0 && self.read[*addr] > 0).count();
+        let untouched = rom_size - executed - data;
+
+        let mut report = format!(
+            "; ROM {:#05x}-{:#05x}: {} executed, {} data, {} untouched bytes ({:.1}% covered)\n",
+            PROGRAM_START_ADDR,
+            self.rom_end.saturating_sub(1),
+            executed,
+            data,
+            untouched,
+            (executed + data) as f64 * 100.0 / rom_size.max(1) as f64
+        );
+
+        let mut addr = PROGRAM_START_ADDR;
+        while addr < self.rom_end {
+            if self.executed[addr] > 0 && addr + 1 < self.rom_end {
+                let opcode = Opcode::from(memory[addr], memory[addr + 1]);
+                report.push_str(&format!(
+                    "X {:03x}: {:04x}  {:<18} ; executed {}\n",
+                    addr,
+                    opcode.value(),
+                    disassemble(&opcode),
+                    self.executed[addr]
+                ));
+                addr += 2;
+            } else if self.executed[addr] > 0 || self.read[addr] > 0 {
+                let (marker, usage) = if self.executed[addr] > 0 {
+                    ("X", format!("executed {}", self.executed[addr]))
+                } else {
+                    ("D", format!("read {}", self.read[addr]))
+                };
+                let data = format!("DB {:#04x}", memory[addr]);
+                report.push_str(&format!("{} {:03x}: {:02x}    {:<18} ; {}\n", marker, addr, memory[addr], data, usage));
+                addr += 1;
+            } else {
+                // collapse untouched runs into a single line
+                let start = addr;
+                while addr < self.rom_end && self.executed[addr] == 0 && self.read[addr] == 0 {
+                    addr += 1;
+                }
+                report.push_str(&format!(". {:03x}-{:03x}: {} untouched bytes\n", start, addr - 1, addr - start));
+            }
+        }
+
+        report
+    }
+
+    // lcov tracefile, a line's hit count is the number of executions and reads of its addresses
+    pub fn lcov(&self, line_map: &LineMap) -> String {
+        // file -> line -> hits
+        let mut files: BTreeMap<&str, BTreeMap<u32, u32>> = BTreeMap::new();
+        for (addr, (file, line)) in line_map.lines.iter() {
+            let addr = *addr as usize;
+            let hits = if addr < MEMORY_SIZE { self.executed[addr] + self.read[addr] } else { 0 };
+            *files.entry(file.as_str()).or_default().entry(*line).or_insert(0) += hits;
+        }
+
+        let mut report = String::from("TN:\n");
+        for (file, lines) in files.iter() {
+            report.push_str(&format!("SF:{}\n", file));
+            for (line, hits) in lines.iter() {
+                report.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            let hit_lines = lines.values().filter(|hits| **hits > 0).count();
+            report.push_str(&format!("LH:{}\nLF:{}\nend_of_record\n", hit_lines, lines.len()));
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Computer;
+
+    // Sprite drawn once: two instructions, a self-jump, a used and an unused data byte
+    fn covered_rom() -> (Coverage, Computer) {
+        let rom = crate::chip8! {
+            ld i, sprite;
+            drw v0, v0, 1;
+            end: jp end;
+            sprite: db 0xff, 0x81
+        };
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.coverage = Some(Coverage::new(rom.len()));
+        computer.load_rom(rom).unwrap();
+        for _ in 0..4 {
+            computer.step();
+        }
+        (computer.coverage.take().unwrap(), computer)
+    }
+
+    #[test]
+    fn marks_executed_data_and_untouched_bytes() {
+        let (coverage, computer) = covered_rom();
+        let listing = coverage.annotated_disassembly(&computer.cpu.bus.memory);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(lines[0], "; ROM 0x200-0x207: 6 executed, 1 data, 1 untouched bytes (87.5% covered)");
+        assert!(lines[1].starts_with("X 200: a206"));
+        assert!(lines[1].ends_with("; executed 1"));
+        assert!(lines[3].ends_with("; executed 1"));
+        assert_eq!(lines[4], "D 206: ff    DB 0xff            ; read 1");
+        assert_eq!(lines[5], ". 207-207: 1 untouched bytes");
+    }
+
+    #[test]
+    fn writes_lcov_for_mapped_lines() {
+        let (coverage, _) = covered_rom();
+        let line_map = LineMap::parse(
+            "# addr file:line\n0x200 game.8o:1\n0x202 game.8o:2\n0x204 game.8o:3\n0x206 data.8o:1\n0x207 data.8o:1\n0x208 data.8o:2\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            coverage.lcov(&line_map),
+            "TN:\n\
+             SF:data.8o\nDA:1,1\nDA:2,0\nLH:1\nLF:2\nend_of_record\n\
+             SF:game.8o\nDA:1,1\nDA:2,1\nDA:3,1\nLH:3\nLF:3\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_line_maps() {
+        assert!(LineMap::parse("0x200").is_err());
+        assert!(LineMap::parse("0x200 game.8o").is_err());
+        assert!(LineMap::parse("0x200 game.8o:x").is_err());
+        assert!(LineMap::parse("zz game.8o:1").is_err());
+        assert!(LineMap::parse("# only comments\n\n").is_ok());
+    }
+}
@@ -1,15 +1,18 @@
-use tinyrand::{Rand, StdRand};
+use tinyrand::{Rand, Seeded, StdRand};
 
+use crate::computer::bus::Bus;
+use crate::computer::fault::Fault;
+use crate::computer::instruction::Reg;
 use crate::computer::opcode::Opcode;
 use crate::computer::display::Display;
-use crate::computer::display::WIDTH as DISPLAY_WIDTH;
 use crate::computer::keyboard::Keyboard;
+use crate::computer::quirks::Quirks;
 
 use core::fmt;
 
 pub struct CPU {
-    // 4KB RAM
-    pub memory: [u8; 4096],
+    // 4KB RAM behind the memory bus
+    pub bus: Bus,
     // 16 general 8-bit registers
     pub regs: [u8; 16],
     // 16-bit index register
@@ -24,6 +27,8 @@ pub struct CPU {
     pub stack: [u16; 16],
     // Current opcode
     pub opcode: Opcode,
+    // Behaviour of the emulated platform
+    pub quirks: Quirks,
     
     rand: StdRand
 }
@@ -31,7 +36,7 @@ pub struct CPU {
 impl CPU {
     pub fn new() -> CPU {
         CPU {
-            memory: [0; 4096],
+            bus: Bus::new(),
             regs: [0; 16],
             i_reg: 0,
             vf: false,
@@ -39,22 +44,28 @@ impl CPU {
             sp: 0,
             stack: [0; 16],
             opcode: Opcode::new(0),
+            quirks: Quirks::default(),
             rand: StdRand::default()
         }
     }
 
+    // Makes `Cxkk` produce a reproducible sequence for the given seed
+    pub fn seed(&mut self, seed: u64) {
+        self.rand = StdRand::seed(seed);
+    }
+
     pub fn reset(&mut self) {
         self.sp = 0;
         self.i_reg = 0;
         self.sp = 0;
         self.regs.fill(0);
         self.stack.fill(0);
-        self.memory.fill(0);
+        self.bus.reset();
         self.pc = super::PROGRAM_START_ADDR;
     }
 
     pub fn fetch_opcode(&mut self) -> Opcode {
-        self.opcode = Opcode::from(self.memory[self.pc], self.memory[self.pc + 1]);
+        self.opcode = self.bus.fetch(self.pc);
         // println!("OPCODE: {:#04x}", self.opcode);
         self.opcode.clone()
     }
@@ -65,170 +76,191 @@ impl CPU {
         self.pc += 2;
     } 
 
-    // 00E0 
-    pub fn return_from_subroutine(&mut self) {
-        self.pc = self.stack[self.sp] as usize;
+    // 00EE
+    pub fn return_from_subroutine(&mut self) -> Result<(), Fault> {
+        if self.sp == 0 {
+            return Err(Fault::StackUnderflow);
+        }
+
         self.sp -= 1;
+        self.pc = self.stack[self.sp] as usize;
+        Ok(())
     }
 
     // 1nnn
-    pub fn jump_to_addr(&mut self) {
-        self.pc = self.opcode.get_nnn() as usize;
+    pub fn jump_to_addr(&mut self, addr: u16) {
+        self.pc = addr as usize;
     }
 
     // 2nnn
-    pub fn call_at_addr(&mut self) {
-        self.sp += 1;
+    pub fn call_at_addr(&mut self, addr: u16) -> Result<(), Fault> {
+        if self.sp == self.stack.len() {
+            return Err(Fault::StackOverflow);
+        }
+
         self.pc += 2;
         self.stack[self.sp] = self.pc as u16;
-        self.jump_to_addr()
+        self.sp += 1;
+        self.jump_to_addr(addr);
+        Ok(())
     }
 
-    pub fn skip_3xkk(&mut self) {
-        if self.get_vx() == self.opcode.get_nn() {
+    // 3xkk
+    pub fn skip_3xkk(&mut self, x: Reg, value: u8) {
+        if self.reg(x) == value {
             self.pc += 2;
         }
         self.pc += 2;
     }
     
     // 4xkk
-    pub fn skip_4xkk(&mut self) {
-        if self.get_vx() != self.opcode.get_nn() {
+    pub fn skip_4xkk(&mut self, x: Reg, value: u8) {
+        if self.reg(x) != value {
             self.pc += 2;
         }
         self.pc += 2;
     }
     
-    pub fn skip_5xy(&mut self) {
-        if self.get_vx() == self.get_vy() {
+    // 5xy0
+    pub fn skip_5xy(&mut self, x: Reg, y: Reg) {
+        if self.reg(x) == self.reg(y) {
             self.pc += 2;
         }
         self.pc += 2;
     }
 
     // 8xy0
-    pub fn vy_to_vx(&mut self) {
-        self.set_vx(self.get_vy());
+    pub fn vy_to_vx(&mut self, x: Reg, y: Reg) {
+        self.set_reg(x, self.reg(y));
         self.pc += 2;
     }
     
     // 8xy1
-    pub fn vx_or_vy(&mut self) {
-        self.set_vx(self.get_vx() | self.get_vy());
+    pub fn vx_or_vy(&mut self, x: Reg, y: Reg) {
+        self.set_reg(x, self.reg(x) | self.reg(y));
+        self.reset_vf();
         self.pc += 2;
     }
 
     // 8xy2
-    pub fn vx_and_vy(&mut self) {
-        self.set_vx(self.get_vx() & self.get_vy());
+    pub fn vx_and_vy(&mut self, x: Reg, y: Reg) {
+        self.set_reg(x, self.reg(x) & self.reg(y));
+        self.reset_vf();
         self.pc += 2;
     }
 
     // 8xy3
-    pub fn vx_xor_vy(&mut self) {
-        self.set_vx(self.get_vx() ^ self.get_vy());
+    pub fn vx_xor_vy(&mut self, x: Reg, y: Reg) {
+        self.set_reg(x, self.reg(x) ^ self.reg(y));
+        self.reset_vf();
         self.pc += 2;
     }
 
     // 8xy4
-    pub fn vx_add_vy(&mut self) {
-        let (value, is_overflow) = self.get_vx().overflowing_add(self.get_vy());
-        self.set_vx(value);
+    pub fn vx_add_vy(&mut self, x: Reg, y: Reg) {
+        let (value, is_overflow) = self.reg(x).overflowing_add(self.reg(y));
+        self.set_reg(x, value);
         
         self.regs[0xF] = if is_overflow { 1 } else { 0 };
         self.pc += 2;
     }
 
     // 8xy5
-    pub fn vx_sub_vy(&mut self) {
-        let (value, is_overflow) = self.get_vx().overflowing_sub(self.get_vy());
-        self.set_vx(value);
+    pub fn vx_sub_vy(&mut self, x: Reg, y: Reg) {
+        let (value, is_overflow) = self.reg(x).overflowing_sub(self.reg(y));
+        self.set_reg(x, value);
 
         self.regs[0xF] = if is_overflow { 0 } else { 1 };
         self.pc += 2;
     }
 
     // 8xy6
-    pub fn vx_shr(&mut self) {
-        let x = self.get_vx();
-        self.set_vx(x >> 1);
+    pub fn vx_shr(&mut self, x: Reg, y: Reg) {
+        let value = self.shift_source(x, y);
+        self.set_reg(x, value >> 1);
 
-        self.regs[0xF] = if x % 2 == 1 { 1 } else { 0 };
+        self.regs[0xF] = if value % 2 == 1 { 1 } else { 0 };
         self.pc += 2;
     }
 
     // 8xy7
-    pub fn vy_sub_vx(&mut self) {
-        let (value, is_overflow) = self.get_vy().overflowing_sub(self.get_vx());
-        self.set_vx(value);
+    pub fn vy_sub_vx(&mut self, x: Reg, y: Reg) {
+        let (value, is_overflow) = self.reg(y).overflowing_sub(self.reg(x));
+        self.set_reg(x, value);
 
         self.regs[0xF] = if is_overflow { 0 } else { 1 };
         self.pc += 2;
     }
 
     // 8xyE
-    pub fn vx_shl(&mut self) {
-        let x = self.get_vx();
-        self.set_vx(x << 1);
-        // Possibly here should be VY modification
+    pub fn vx_shl(&mut self, x: Reg, y: Reg) {
+        let value = self.shift_source(x, y);
+        self.set_reg(x, value << 1);
         
-        self.regs[0xF] = if x & 0b10000000 != 0 { 1 } else { 0 };
+        self.regs[0xF] = if value & 0b10000000 != 0 { 1 } else { 0 };
         self.pc += 2;
     }
 
-    pub fn skip_9xy(&mut self) {
-        if self.get_vx() != self.get_vy() {
+    // 9xy0
+    pub fn skip_9xy(&mut self, x: Reg, y: Reg) {
+        if self.reg(x) != self.reg(y) {
             self.pc += 2;
         }
         self.pc += 2;
     }
 
     // 6xkk
-    pub fn put_value_to_vx(&mut self) {
-        self.set_vx(self.opcode.get_nn());
+    pub fn put_value_to_vx(&mut self, x: Reg, value: u8) {
+        self.set_reg(x, value);
         self.pc += 2;
     }
     
     // 7xkk
-    pub fn add_value_to_vx(&mut self) {
-        let value: u8 = self.opcode.get_nn();
-        // println!("   ADD {:#04x} + {:#04x}", self.get_vx(), value);
-        self.set_vx(self.get_vx().overflowing_add(value).0);
+    pub fn add_value_to_vx(&mut self, x: Reg, value: u8) {
+        self.set_reg(x, self.reg(x).overflowing_add(value).0);
         self.pc += 2;
     }
 
     // Annn
-    pub fn set_i_reg(&mut self) {
-        self.i_reg = self.opcode.get_nnn();
+    pub fn set_i_reg(&mut self, addr: u16) {
+        self.i_reg = addr;
         self.pc += 2;
     } 
 
-    // Bnnn
-    pub fn jump_to_addr_offset(&mut self) {
-        let addr: u16 = self.opcode.get_nnn() + self.regs[0] as u16;
+    // Bnnn, or Bxnn with the jumping quirk
+    pub fn jump_to_addr_offset(&mut self, addr: u16) {
+        let offset_reg = if self.quirks.jumping { (addr >> 8) as Reg } else { 0 };
+        let addr: u16 = addr + self.reg(offset_reg) as u16;
         self.pc = addr.into();
     }
 
-    // Cxnn
-    pub fn add_random_to_vx(&mut self) {
+    // Cxkk
+    pub fn add_random_to_vx(&mut self, x: Reg, mask: u8) {
         let random_number = self.rand.next_lim_u16(0xFF) as u8;
-        self.set_vx(random_number & self.opcode.get_nn());
+        self.set_reg(x, random_number & mask);
         self.pc += 2;
     }
 
     // Dxyn
-    pub fn draw_sprite(&mut self, display: &mut Display) {
-        let x = self.get_vx();// & (DISPLAY_WIDTH - 1);
-        let y = self.get_vy();// & (DISPLAY_HEIGHT - 1);
-        let height: u8 = self.opcode.get_z();
+    pub fn draw_sprite(&mut self, display: &mut Display, x: Reg, y: Reg, height: u8) {
+        // start position always wraps, the sprite itself is clipped or wrapped
+        let (width, screen_height) = (display.width(), display.height());
+        let x = self.reg(x) as usize % width;
+        let y = self.reg(y) as usize % screen_height;
         self.regs[0xF] = 0;
         
-        for y_line in 0..height {
-            let pixel = self.memory[(self.i_reg + y_line as u16) as usize];
+        for y_line in 0..height as usize {
+            let pixel = self.bus.read(self.i_reg as usize + y_line);
+            if self.quirks.clipping && y + y_line >= screen_height {
+                break;
+            }
 
-            for x_line in 0..8u16 {
+            for x_line in 0..8usize {
+                if self.quirks.clipping && x + x_line >= width {
+                    break;
+                }
                 if (pixel & (0x80 >> x_line)) != 0 {
-                    let position = (x as u16 + x_line + ((y + y_line) as u16 * DISPLAY_WIDTH as u16)) as usize;
+                    let position = (x + x_line) % width + ((y + y_line) % screen_height) * width;
                     
                     if display.memory[position] == 1 {
                         self.regs[0xF] = 1;
@@ -243,85 +275,95 @@ impl CPU {
     }
 
     // Ex9E
-    pub fn skip_on_keydown(&mut self, keyboard: &Keyboard) {
-        println!("{}", self.opcode.get_x());
-        if keyboard.keys[self.opcode.get_x() as usize] {
-            println!("skip on keydown");
+    pub fn skip_on_keydown(&mut self, keyboard: &Keyboard, x: Reg) {
+        if keyboard.keys[(self.reg(x) & 0xF) as usize] {
             self.pc += 2;
         }
         self.pc += 2;
     }
 
     // ExA1
-    pub fn skip_on_keyup(&mut self, keyboard: &Keyboard) {
-        if !keyboard.keys[self.opcode.get_x() as usize] {
-            // println!("skip on keyUP");
+    pub fn skip_on_keyup(&mut self, keyboard: &Keyboard, x: Reg) {
+        if !keyboard.keys[(self.reg(x) & 0xF) as usize] {
             self.pc += 2;
         }
         self.pc += 2;
     }
 
     // Fx1E
-    pub fn add_vx_to_i(&mut self) {
-        self.i_reg += self.get_vx() as u16;
+    pub fn add_vx_to_i(&mut self, x: Reg) {
+        self.i_reg = self.i_reg.wrapping_add(self.reg(x) as u16);
         self.pc += 2;
     }
 
     // Fx29
-    pub fn set_font_char_addr(&mut self) {
-        self.i_reg = (self.get_vx() as u16) * 0x5;
+    pub fn set_font_char_addr(&mut self, x: Reg) {
+        self.i_reg = (self.reg(x) as u16) * 0x5;
         self.pc += 2;
     }
 
     // Fx33
-    pub fn vx_decimal_to_ireg(&mut self) {
-        let value = self.get_vx();
-        self.memory[self.i_reg as usize] = value / 100;
-        self.memory[(self.i_reg + 1) as usize] = (value / 10) % 10;
-        self.memory[(self.i_reg + 2) as usize] = (value % 100) % 10;
+    pub fn vx_decimal_to_ireg(&mut self, x: Reg) {
+        let value = self.reg(x);
+        self.bus.write(self.i_reg as usize, value / 100);
+        self.bus.write(self.i_reg as usize + 1, (value / 10) % 10);
+        self.bus.write(self.i_reg as usize + 2, (value % 100) % 10);
         self.pc += 2;
     }
 
     // Fx55
-    pub fn store_regs_in_memory(&mut self) {
-        let x_index: usize = self.opcode.get_x().into();
+    pub fn store_regs_in_memory(&mut self, x: Reg) {
+        let x_index = x as usize;
 
         for reg_index in 0..=x_index  {
-            self.memory[self.i_reg as usize + reg_index as usize] = self.regs[reg_index as usize];
+            self.bus.write(self.i_reg as usize + reg_index, self.regs[reg_index]);
         }
         
-        // CONFIGURABLE (new/old systems):
-        // self.i_reg += x_index + 1;
+        if self.quirks.memory_increment {
+            self.i_reg = self.i_reg.wrapping_add(x_index as u16 + 1);
+        }
         self.pc += 2;
     }
 
     // Fx65
-    pub fn store_memory_in_regs(&mut self) {
-        let x_index: usize = self.opcode.get_x().into();
+    pub fn store_memory_in_regs(&mut self, x: Reg) {
+        let x_index = x as usize;
     
         for reg_index in 0..=x_index  {
-            self.regs[reg_index] = self.memory[self.i_reg as usize + reg_index];
+            self.regs[reg_index] = self.bus.read(self.i_reg as usize + reg_index);
         }
         
-        // CONFIGURABLE (new/old systems):
-        // self.i_reg += x_index + 1;
+        if self.quirks.memory_increment {
+            self.i_reg = self.i_reg.wrapping_add(x_index as u16 + 1);
+        }
         self.pc += 2;
     }
 
     // === Helpers ===
 
-    pub fn get_vx(&self) -> u8 {
-        self.regs[self.opcode.get_x() as usize]
+    pub fn reg(&self, x: Reg) -> u8 {
+        self.regs[x as usize]
     }
 
-    pub fn set_vx(&mut self, value: u8) {
-        let reg_id = self.opcode.get_x() as usize;
-        // println!("   SET V{} - {:#04x}", reg_id, value);
-        self.regs[reg_id] = value;
+    pub fn set_reg(&mut self, x: Reg, value: u8) {
+        self.regs[x as usize] = value;
     }
-    
-    fn get_vy(&self) -> u8 {
-        self.regs[self.opcode.get_y() as usize]
+
+    // 8xy1/8xy2/8xy3 clear VF on the original interpreter
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.regs[0xF] = 0;
+        }
+    }
+
+    // Value shifted by 8xy6/8xyE
+    fn shift_source(&self, x: Reg, y: Reg) -> u8 {
+        if self.quirks.shifting { self.reg(x) } else { self.reg(y) }
+    }
+
+    // Return addresses of active subroutine calls, innermost last
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
     }
 }
 
@@ -336,4 +378,469 @@ impl fmt::Debug for CPU {
          .field("stack", &self.stack)
          .finish()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::computer::quirks::Platform;
+    use crate::computer::{Computer, Status, MAX_ROM_SIZE, PROGRAM_START_ADDR};
+    use crate::computer::fault::Fault;
+
+    // Computer with `rom` loaded, quirks of the original interpreter
+    fn load_bytes(rom: Vec<u8>) -> Computer {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer.cpu.quirks = Platform::Chip8.quirks();
+        computer
+    }
+
+    fn load(program: &[u16]) -> Computer {
+        load_bytes(program.iter().flat_map(|word| word.to_be_bytes()).collect())
+    }
+
+    // Runs `steps` instructions of `program`
+    fn run(program: &[u16], steps: usize) -> Computer {
+        let mut computer = load(program);
+        run_steps(&mut computer, steps);
+        computer
+    }
+
+    fn run_steps(computer: &mut Computer, steps: usize) {
+        for _ in 0..steps {
+            computer.emulate_cycle().unwrap();
+        }
+    }
+
+    fn pc_after(program: &[u16], steps: usize) -> usize {
+        run(program, steps).cpu.pc - PROGRAM_START_ADDR
+    }
+
+    fn pixel(computer: &Computer, x: usize, y: usize) -> u8 {
+        computer.display.memory[y * computer.display.width() + x]
+    }
+
+    #[test]
+    fn rom_must_fit_into_memory() {
+        let mut computer = Computer::new();
+        assert!(computer.load_rom(vec![0xFF; MAX_ROM_SIZE]).is_ok());
+        assert_eq!(computer.cpu.bus.memory.last(), Some(&0xFF));
+        assert!(computer.load_rom(vec![0; MAX_ROM_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn cls_clears_display() {
+        let mut computer = load(&[0x00E0]);
+        computer.display.memory.fill(1);
+        run_steps(&mut computer, 1);
+        assert!(computer.display.memory.iter().all(|pixel| *pixel == 0));
+        assert_eq!(computer.cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn call_and_return() {
+        let mut computer = load_bytes(crate::chip8! {
+            call subroutine;
+            ld v1, 2;
+            end: jp end;
+            subroutine: ld v0, 1;
+            ret
+        });
+        run_steps(&mut computer, 4);
+        assert_eq!(computer.cpu.regs[0], 1);
+        assert_eq!(computer.cpu.regs[1], 2);
+        assert_eq!(computer.cpu.sp, 0);
+        assert_eq!(computer.cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn call_uses_first_stack_entry() {
+        let computer = run(&[0x2202], 1);
+        assert_eq!(computer.cpu.stack[0], 0x202);
+        assert_eq!(computer.cpu.sp, 1);
+        assert_eq!(computer.cpu.call_stack(), &[0x202]);
+    }
+
+    #[test]
+    fn sixteen_nested_calls_fit_the_stack() {
+        // 200: CALL 200, recursing forever
+        let mut computer = load(&[0x2200]);
+        run_steps(&mut computer, 16);
+        assert_eq!(computer.cpu.sp, 16);
+        assert_eq!(computer.emulate_cycle(), Err(Fault::StackOverflow));
+    }
+
+    #[test]
+    fn return_with_empty_stack_faults() {
+        let mut computer = load(&[0x00EE]);
+        assert_eq!(computer.emulate_cycle(), Err(Fault::StackUnderflow));
+    }
+
+    #[test]
+    fn unknown_opcode_faults() {
+        for opcode in [0x0123, 0x800F, 0xE000, 0xF0FF] {
+            let mut computer = load(&[opcode]);
+            assert_eq!(computer.emulate_cycle(), Err(Fault::UnknownOpcode(opcode)));
+        }
+    }
+
+    #[test]
+    fn jp_jumps() {
+        assert_eq!(run(&[0x1ABC], 1).cpu.pc, 0xABC);
+    }
+
+    #[test]
+    fn se_vx_byte() {
+        assert_eq!(pc_after(&[0x6005, 0x3005], 2), 6);
+        assert_eq!(pc_after(&[0x6005, 0x3006], 2), 4);
+    }
+
+    #[test]
+    fn sne_vx_byte() {
+        assert_eq!(pc_after(&[0x6005, 0x4005], 2), 4);
+        assert_eq!(pc_after(&[0x6005, 0x4006], 2), 6);
+    }
+
+    #[test]
+    fn se_vx_vy() {
+        assert_eq!(pc_after(&[0x6005, 0x6105, 0x5010], 3), 8);
+        assert_eq!(pc_after(&[0x6005, 0x6106, 0x5010], 3), 6);
+    }
+
+    #[test]
+    fn sne_vx_vy() {
+        assert_eq!(pc_after(&[0x6005, 0x6105, 0x9010], 3), 6);
+        assert_eq!(pc_after(&[0x6005, 0x6106, 0x9010], 3), 8);
+    }
+
+    #[test]
+    fn ld_vx_byte() {
+        assert_eq!(run(&[0x6AFF], 1).cpu.regs[0xA], 0xFF);
+    }
+
+    #[test]
+    fn add_vx_byte_wraps_without_carry() {
+        let computer = run(&[0x60FF, 0x6F05, 0x7002], 3);
+        assert_eq!(computer.cpu.regs[0], 0x01);
+        assert_eq!(computer.cpu.regs[0xF], 0x05);
+    }
+
+    #[test]
+    fn ld_vx_vy() {
+        assert_eq!(run(&[0x6142, 0x8010], 2).cpu.regs[0], 0x42);
+    }
+
+    #[test]
+    fn logic_ops() {
+        assert_eq!(run(&[0x60F0, 0x610F, 0x8011], 3).cpu.regs[0], 0xFF);
+        assert_eq!(run(&[0x60F3, 0x613F, 0x8012], 3).cpu.regs[0], 0x33);
+        assert_eq!(run(&[0x60F3, 0x613F, 0x8013], 3).cpu.regs[0], 0xCC);
+    }
+
+    #[test]
+    fn logic_ops_vf_reset_quirk() {
+        for opcode in [0x8011, 0x8012, 0x8013] {
+            assert_eq!(run(&[0x6F05, opcode], 2).cpu.regs[0xF], 0);
+
+            let mut computer = load(&[0x6F05, opcode]);
+            computer.cpu.quirks = Platform::Schip.quirks();
+            run_steps(&mut computer, 2);
+            assert_eq!(computer.cpu.regs[0xF], 5);
+        }
+    }
+
+    #[test]
+    fn add_vx_vy_sets_carry() {
+        let computer = run(&[0x60FF, 0x6102, 0x8014], 3);
+        assert_eq!(computer.cpu.regs[0], 0x01);
+        assert_eq!(computer.cpu.regs[0xF], 1);
+
+        let computer = run(&[0x6010, 0x6102, 0x8014], 3);
+        assert_eq!(computer.cpu.regs[0], 0x12);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+    }
+
+    #[test]
+    fn sub_vx_vy_sets_not_borrow() {
+        let computer = run(&[0x6005, 0x6103, 0x8015], 3);
+        assert_eq!(computer.cpu.regs[0], 2);
+        assert_eq!(computer.cpu.regs[0xF], 1);
+
+        let computer = run(&[0x6003, 0x6105, 0x8015], 3);
+        assert_eq!(computer.cpu.regs[0], 0xFE);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+
+        // equal values don't borrow
+        assert_eq!(run(&[0x6003, 0x6103, 0x8015], 3).cpu.regs[0xF], 1);
+    }
+
+    #[test]
+    fn subn_vx_vy_sets_not_borrow() {
+        let computer = run(&[0x6003, 0x6105, 0x8017], 3);
+        assert_eq!(computer.cpu.regs[0], 2);
+        assert_eq!(computer.cpu.regs[0xF], 1);
+
+        let computer = run(&[0x6005, 0x6103, 0x8017], 3);
+        assert_eq!(computer.cpu.regs[0], 0xFE);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+    }
+
+    #[test]
+    fn flag_wins_when_vf_is_the_target() {
+        assert_eq!(run(&[0x6FFF, 0x6102, 0x8F14], 3).cpu.regs[0xF], 1);
+        assert_eq!(run(&[0x6F01, 0x6102, 0x8F15], 3).cpu.regs[0xF], 0);
+        assert_eq!(run(&[0x6F81, 0x8FF6], 2).cpu.regs[0xF], 1);
+    }
+
+    #[test]
+    fn shifts_use_vy() {
+        let computer = run(&[0x6000, 0x6105, 0x8016], 3);
+        assert_eq!(computer.cpu.regs[0], 0x02);
+        assert_eq!(computer.cpu.regs[0xF], 1);
+
+        let computer = run(&[0x6000, 0x6181, 0x801E], 3);
+        assert_eq!(computer.cpu.regs[0], 0x02);
+        assert_eq!(computer.cpu.regs[0xF], 1);
+
+        let computer = run(&[0x6000, 0x6140, 0x801E], 3);
+        assert_eq!(computer.cpu.regs[0], 0x80);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+    }
+
+    #[test]
+    fn shifts_use_vx_with_shifting_quirk() {
+        let mut computer = load(&[0x6004, 0x61FF, 0x8016, 0x801E]);
+        computer.cpu.quirks = Platform::Schip.quirks();
+        run_steps(&mut computer, 3);
+        assert_eq!(computer.cpu.regs[0], 0x02);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+        run_steps(&mut computer, 1);
+        assert_eq!(computer.cpu.regs[0], 0x04);
+    }
+
+    #[test]
+    fn ld_i_addr() {
+        assert_eq!(run(&[0xA123], 1).cpu.i_reg, 0x123);
+    }
+
+    #[test]
+    fn jp_v0_addr() {
+        assert_eq!(run(&[0x6010, 0x6120, 0xB300], 3).cpu.pc, 0x310);
+    }
+
+    #[test]
+    fn jp_vx_addr_with_jumping_quirk() {
+        let mut computer = load(&[0x6010, 0x6320, 0xB300]);
+        computer.cpu.quirks = Platform::Schip.quirks();
+        run_steps(&mut computer, 3);
+        assert_eq!(computer.cpu.pc, 0x320);
+    }
+
+    #[test]
+    fn rnd_masks_random_byte() {
+        let computer = run(&[0xC00F, 0xC100], 2);
+        assert!(computer.cpu.regs[0] <= 0x0F);
+        assert_eq!(computer.cpu.regs[1], 0);
+    }
+
+    #[test]
+    fn rnd_is_reproducible_with_seed() {
+        let values: Vec<u8> = (0..2)
+            .map(|_| {
+                let mut computer = load(&[0xC0FF]);
+                computer.cpu.seed(42);
+                run_steps(&mut computer, 1);
+                computer.cpu.regs[0]
+            })
+            .collect();
+        assert_eq!(values[0], values[1]);
+    }
+
+    #[test]
+    fn drw_draws_and_detects_collision() {
+        // font glyph 0 at (1, 2), then again to erase it
+        let mut computer = load(&[0x6001, 0x6102, 0xA000, 0xD015, 0xD015]);
+        run_steps(&mut computer, 4);
+        assert_eq!(pixel(&computer, 1, 2), 1);
+        assert_eq!(pixel(&computer, 4, 2), 1);
+        assert_eq!(pixel(&computer, 5, 2), 0);
+        assert_eq!(computer.cpu.regs[0xF], 0);
+
+        computer.waiting_vblank = false;
+        run_steps(&mut computer, 1);
+        assert!(computer.display.memory.iter().all(|pixel| *pixel == 0));
+        assert_eq!(computer.cpu.regs[0xF], 1);
+    }
+
+    #[test]
+    fn drw_wraps_start_position() {
+        // x = 64 + 2, y = 32 + 1
+        let computer = run(&[0x6042, 0x6121, 0xA000, 0xD011], 4);
+        assert_eq!(pixel(&computer, 2, 1), 1);
+    }
+
+    #[test]
+    fn drw_clips_at_edges() {
+        // glyph 0 at (62, 30), only its top left part is visible
+        let computer = run(&[0x603E, 0x611E, 0xA000, 0xD015], 4);
+        assert_eq!(pixel(&computer, 62, 30), 1);
+        assert_eq!(pixel(&computer, 63, 30), 1);
+        assert_eq!(pixel(&computer, 0, 30), 0);
+        assert_eq!(pixel(&computer, 62, 0), 0);
+    }
+
+    #[test]
+    fn drw_wraps_at_edges_without_clipping_quirk() {
+        let mut computer = load(&[0x603E, 0x611E, 0xA000, 0xD015]);
+        computer.cpu.quirks = Platform::XoChip.quirks();
+        run_steps(&mut computer, 4);
+        assert_eq!(pixel(&computer, 63, 30), 1);
+        assert_eq!(pixel(&computer, 0, 30), 1);
+        assert_eq!(pixel(&computer, 62, 0), 1);
+    }
+
+    #[test]
+    fn drw_uses_display_size() {
+        // x = 66 is on screen at 128x64, the glyph's right edge clips at 127
+        let mut computer = load(&[0x00FF, 0x6042, 0x617E, 0xA000, 0xD015, 0x607E, 0xD015]);
+        run_steps(&mut computer, 5);
+        assert_eq!(pixel(&computer, 66, 62), 1);
+        assert_eq!(pixel(&computer, 2, 62), 0);
+
+        computer.waiting_vblank = false;
+        run_steps(&mut computer, 2);
+        assert_eq!(pixel(&computer, 127, 62), 1);
+        assert_eq!(pixel(&computer, 0, 62), 0);
+    }
+
+    #[test]
+    fn switches_resolution_while_running() {
+        let mut computer = load(&[0x00FF, 0x6064, 0x613A, 0xA000, 0xD015, 0x00FE, 0xD015]);
+        run_steps(&mut computer, 5);
+        assert_eq!((computer.display.width(), computer.display.height()), (128, 64));
+        assert_eq!(pixel(&computer, 100, 58), 1);
+
+        // back to 64x32 with a clear screen, the same coordinates wrap around
+        computer.waiting_vblank = false;
+        run_steps(&mut computer, 1);
+        assert_eq!((computer.display.width(), computer.display.height()), (64, 32));
+        assert!(computer.should_clear_screen);
+        assert!(computer.display.memory.iter().all(|pixel| *pixel == 0));
+        run_steps(&mut computer, 1);
+        assert_eq!(pixel(&computer, 100 % 64, 58 % 32), 1);
+
+        computer.reset();
+        assert_eq!(computer.display.width(), 64);
+    }
+
+    #[test]
+    fn drw_waits_for_vblank_with_display_wait_quirk() {
+        let mut computer = load(&[0xD001, 0x6001]);
+        run_steps(&mut computer, 1);
+        assert!(computer.waiting_vblank);
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.cpu.regs[0], 0);
+
+        computer.tick_timers();
+        computer.step();
+        assert_eq!(computer.cpu.regs[0], 1);
+    }
+
+    #[test]
+    fn skp_checks_key_in_vx() {
+        let mut computer = load(&[0x6105, 0xE19E]);
+        computer.set_key(5, true);
+        run_steps(&mut computer, 2);
+        assert_eq!(computer.cpu.pc, 0x206);
+
+        // key 1 (the register number) is not what's checked
+        let mut computer = load(&[0x6105, 0xE19E]);
+        computer.set_key(1, true);
+        run_steps(&mut computer, 2);
+        assert_eq!(computer.cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn sknp_checks_key_in_vx() {
+        let mut computer = load(&[0x610A, 0xE1A1]);
+        computer.set_key(0xA, true);
+        run_steps(&mut computer, 2);
+        assert_eq!(computer.cpu.pc, 0x204);
+
+        let mut computer = load(&[0x610A, 0xE1A1]);
+        computer.set_key(1, true);
+        run_steps(&mut computer, 2);
+        assert_eq!(computer.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn ld_vx_dt_and_timers() {
+        let computer = run(&[0x6009, 0xF015, 0xF018, 0xF107], 4);
+        assert_eq!(computer.delay_timer, 9);
+        assert_eq!(computer.sound_timer, 9);
+        assert_eq!(computer.cpu.regs[1], 9);
+    }
+
+    #[test]
+    fn timers_stop_at_zero() {
+        let mut computer = run(&[0x6001, 0xF015], 2);
+        computer.tick_timers();
+        computer.tick_timers();
+        assert_eq!(computer.delay_timer, 0);
+    }
+
+    #[test]
+    fn ld_vx_k_waits_for_key_release() {
+        let mut computer = load(&[0xF30A, 0x6001]);
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.step(), Status::WaitingKey);
+
+        computer.set_key(7, true);
+        assert_eq!(computer.step(), Status::WaitingKey);
+        computer.set_key(7, false);
+        assert_eq!(computer.cpu.regs[3], 7);
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.cpu.regs[0], 1);
+    }
+
+    #[test]
+    fn add_i_vx() {
+        assert_eq!(run(&[0xA0FF, 0x6002, 0xF01E], 3).cpu.i_reg, 0x101);
+    }
+
+    #[test]
+    fn ld_f_vx_points_to_font() {
+        assert_eq!(run(&[0x600A, 0xF029], 2).cpu.i_reg, 50);
+    }
+
+    #[test]
+    fn ld_b_vx_stores_bcd() {
+        let computer = run(&[0x60FE, 0xA300, 0xF033], 3);
+        assert_eq!(computer.cpu.bus.memory[0x300..0x303], [2, 5, 4]);
+        let computer = run(&[0x6007, 0xA300, 0xF033], 3);
+        assert_eq!(computer.cpu.bus.memory[0x300..0x303], [0, 0, 7]);
+    }
+
+    #[test]
+    fn ld_i_vx_stores_registers() {
+        let computer = run(&[0x6001, 0x6102, 0x6203, 0xA300, 0xF155], 5);
+        assert_eq!(computer.cpu.bus.memory[0x300..0x303], [1, 2, 0]);
+        assert_eq!(computer.cpu.i_reg, 0x302);
+    }
+
+    #[test]
+    fn ld_vx_i_loads_registers() {
+        let mut computer = load(&[0x62FF, 0xA300, 0xF165]);
+        computer.cpu.bus.memory[0x300..0x303].copy_from_slice(&[7, 8, 9]);
+        run_steps(&mut computer, 3);
+        assert_eq!(computer.cpu.regs[..3], [7, 8, 0xFF]);
+        assert_eq!(computer.cpu.i_reg, 0x302);
+    }
+
+    #[test]
+    fn memory_ops_keep_i_without_memory_increment_quirk() {
+        let mut computer = load(&[0xA300, 0xF255, 0xF265]);
+        computer.cpu.quirks = Platform::Schip.quirks();
+        run_steps(&mut computer, 3);
+        assert_eq!(computer.cpu.i_reg, 0x300);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::computer::bus::parse_addr;
+use crate::computer::expr::{Expr, State};
+
+pub struct Breakpoint {
+    // Address to stop at, condition is checked on every instruction if None
+    pub addr: Option<u16>,
+    pub condition: Option<Expr>,
+    // Tracepoints print the message instead of pausing
+    pub message: Option<Message>,
+    // Number of times the address (any address without one) was reached, available as `hits` in conditions
+    pub hits: u32,
+}
+
+impl Breakpoint {
+    // Parses `[ADDR] [if CONDITION]`, e.g. `0x2a4 if v3 > 10`
+    pub fn parse(spec: &str) -> Result<Breakpoint, String> {
+        let spec = spec.trim();
+        let (addr, condition) = match spec.strip_prefix("if ") {
+            Some(condition) => ("", Some(condition)),
+            None => match spec.split_once(" if ") {
+                Some((addr, condition)) => (addr, Some(condition)),
+                None => (spec, None),
+            },
+        };
+
+        let addr = match addr.trim() {
+            "" => None,
+            addr => Some(parse_addr(addr)?),
+        };
+        let condition = condition.map(Expr::parse).transpose()?;
+
+        if addr.is_none() && condition.is_none() {
+            return Err(format!("Breakpoint needs an address or a condition: {spec}"));
+        }
+
+        Ok(Breakpoint { addr, condition, message: None, hits: 0 })
+    }
+
+    // Parses `[ADDR] [if CONDITION] => MESSAGE`, e.g. `0x2a4 => v3={v3} i={i:x}`
+    pub fn parse_tracepoint(spec: &str) -> Result<Breakpoint, String> {
+        let (spec, message) = spec
+            .split_once("=>")
+            .ok_or(format!("Tracepoint needs a `=> message`: {spec}"))?;
+
+        let mut breakpoint = Breakpoint::parse(spec)?;
+        breakpoint.message = Some(Message::parse(message.trim())?);
+        Ok(breakpoint)
+    }
+}
+
+enum MessagePart {
+    Text(String),
+    Value { expr: Expr, hex: bool },
+}
+
+// Tracepoint message with `{expr}` (decimal) and `{expr:x}` (hex) placeholders
+pub struct Message {
+    parts: Vec<MessagePart>,
+}
+
+impl Message {
+    pub fn parse(template: &str) -> Result<Message, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(MessagePart::Text(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or(format!("Unclosed `{{` in message: {template}"))?;
+            let placeholder = &rest[start + 1..start + end];
+            let (source, hex) = match placeholder.strip_suffix(":x") {
+                Some(source) => (source, true),
+                None => (placeholder, false),
+            };
+            parts.push(MessagePart::Value { expr: Expr::parse(source)?, hex });
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(MessagePart::Text(rest.to_string()));
+        }
+
+        Ok(Message { parts })
+    }
+
+    pub fn format(&self, state: &State) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                MessagePart::Text(text) => text.clone(),
+                MessagePart::Value { expr, hex: true } => format!("{:#x}", expr.eval(state)),
+                MessagePart::Value { expr, hex: false } => expr.eval(state).to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct Debugger {
+    // Emulation is suspended while paused
+    pub paused: bool,
+    pub breakpoints: Vec<Breakpoint>,
+    // Run a single instruction while paused
+    step_requested: bool,
+    // PC of the last breakpoint hit, it doesn't pause again when execution continues from there
+    break_pc: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger { paused: false, breakpoints: Vec::new(), step_requested: false, break_pc: None }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    // Checks breakpoints against the state before the instruction at PC runs,
+    // pauses if any breakpoint is hit, returns tracepoint and breakpoint messages
+    pub fn check_breakpoints(&mut self, state: &State) -> Vec<String> {
+        let pc = state.cpu.pc;
+        let mut should_pause = false;
+        let mut messages = Vec::new();
+        if self.break_pc.take() == Some(pc) {
+            return messages;
+        }
+
+        for breakpoint in self.breakpoints.iter_mut() {
+            if breakpoint.addr.is_some_and(|addr| addr as usize != pc) {
+                continue;
+            }
+
+            // without an address every instruction is a hit
+            breakpoint.hits += 1;
+            let state = State { hits: breakpoint.hits, ..*state };
+
+            if let Some(condition) = &breakpoint.condition {
+                if !condition.is_true(&state) {
+                    continue;
+                }
+            }
+
+            match &breakpoint.message {
+                Some(message) => messages.push(format!("[{:#05x}] {}", pc, message.format(&state))),
+                None => {
+                    messages.push(format!("Breakpoint hit at {:#05x}", pc));
+                    should_pause = true;
+                },
+            }
+        }
+
+        if should_pause {
+            self.pause();
+            self.break_pc = Some(pc);
+        }
+        messages
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.step_requested = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.step_requested = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.step_requested = true;
+    }
+
+    // Returns true if the next instruction may be executed
+    pub fn should_run(&mut self) -> bool {
+        if !self.paused {
+            return true;
+        }
+
+        let should_step = self.step_requested;
+        self.step_requested = false;
+        should_step
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::{Computer, Status};
+
+    fn computer(rom: Vec<u8>, breakpoint: Breakpoint) -> Computer {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer.debugger.add_breakpoint(breakpoint);
+        computer
+    }
+
+    #[test]
+    fn breakpoint_pauses_before_the_instruction() {
+        let rom = crate::chip8! {
+            ld v0, 1;
+            ld v0, 2
+        };
+        let mut computer = computer(rom, Breakpoint::parse("0x200").unwrap());
+
+        assert_eq!(computer.step(), Status::Paused);
+        assert_eq!(computer.cpu.pc, 0x200);
+        assert_eq!(computer.cpu.regs[0], 0);
+        assert_eq!(computer.messages, ["Breakpoint hit at 0x200"]);
+
+        // resuming runs the instruction at the breakpoint instead of stopping there again
+        computer.debugger.resume();
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.cpu.regs[0], 1);
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.debugger.breakpoints[0].hits, 1);
+    }
+
+    #[test]
+    fn breakpoint_fires_again_on_the_next_visit() {
+        let rom = crate::chip8! {
+            start: add v0, 1;
+            jp start
+        };
+        let mut computer = computer(rom, Breakpoint::parse("0x200 if v0 == 2").unwrap());
+
+        while computer.step() == Status::Running {}
+        assert_eq!(computer.cpu.regs[0], 2);
+        assert_eq!(computer.debugger.breakpoints[0].hits, 3);
+    }
+
+    #[test]
+    fn breakpoint_without_address_counts_every_instruction() {
+        let rom = crate::chip8! {
+            start: add v0, 1;
+            jp start
+        };
+        let mut computer = computer(rom, Breakpoint::parse("if hits > 2").unwrap());
+
+        // paused before the third instruction
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.step(), Status::Running);
+        assert_eq!(computer.step(), Status::Paused);
+        assert_eq!(computer.cpu.pc, 0x200);
+        assert_eq!(computer.cpu.regs[0], 1);
+        assert_eq!(computer.messages, ["Breakpoint hit at 0x200"]);
+    }
+
+    #[test]
+    fn tracepoint_formats_message_without_pausing() {
+        let rom = crate::chip8! {
+            ld v3, 0x2a;
+            ld i, 0x300
+        };
+        let tracepoint = Breakpoint::parse_tracepoint("0x202 => v3={v3} i={i:x}").unwrap();
+        let mut computer = computer(rom, tracepoint);
+
+        computer.step();
+        computer.step();
+        assert!(!computer.debugger.paused);
+        assert_eq!(computer.messages, ["[0x202] v3=42 i=0x0"]);
+    }
+
+    #[test]
+    fn parses_breakpoint_specs() {
+        assert!(Breakpoint::parse("0x2a4 if v3 > 10").is_ok_and(|bp| bp.addr == Some(0x2A4) && bp.condition.is_some()));
+        assert!(Breakpoint::parse("if hits > 2").is_ok_and(|bp| bp.addr.is_none()));
+        assert!(Breakpoint::parse("").is_err());
+        assert!(Breakpoint::parse_tracepoint("0x200").is_err());
+        assert!(Message::parse("{v0").is_err());
+    }
+}
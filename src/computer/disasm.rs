@@ -0,0 +1,49 @@
+use crate::computer::instruction::{decode, Instruction};
+use crate::computer::opcode::Opcode;
+
+// Returns mnemonic for the opcode in Cowgod's notation, e.g. `LD V0, 0x0C`
+pub fn disassemble(opcode: &Opcode) -> String {
+    use Instruction::*;
+
+    match decode(opcode) {
+        Cls => String::from("CLS"),
+        Ret => String::from("RET"),
+        Low => String::from("LOW"),
+        High => String::from("HIGH"),
+        Sys(addr) => format!("SYS {:#05x}", addr),
+        Jp(addr) => format!("JP {:#05x}", addr),
+        Call(addr) => format!("CALL {:#05x}", addr),
+        SeByte(x, value) => format!("SE V{:X}, {:#04x}", x, value),
+        SneByte(x, value) => format!("SNE V{:X}, {:#04x}", x, value),
+        SeReg(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        LdByte(x, value) => format!("LD V{:X}, {:#04x}", x, value),
+        AddByte(x, value) => format!("ADD V{:X}, {:#04x}", x, value),
+        LdReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Shr(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Subn(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Shl(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        SneReg(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        LdI(addr) => format!("LD I, {:#05x}", addr),
+        JpV0(addr) => format!("JP V0, {:#05x}", addr),
+        Rnd(x, mask) => format!("RND V{:X}, {:#04x}", x, mask),
+        Drw(x, y, height) => format!("DRW V{:X}, V{:X}, {}", x, y, height),
+        Skp(x) => format!("SKP V{:X}", x),
+        Sknp(x) => format!("SKNP V{:X}", x),
+        LdRegDt(x) => format!("LD V{:X}, DT", x),
+        LdRegK(x) => format!("LD V{:X}, K", x),
+        LdDtReg(x) => format!("LD DT, V{:X}", x),
+        LdStReg(x) => format!("LD ST, V{:X}", x),
+        AddIReg(x) => format!("ADD I, V{:X}", x),
+        LdFReg(x) => format!("LD F, V{:X}", x),
+        LdBReg(x) => format!("LD B, V{:X}", x),
+        LdMemRegs(x) => format!("LD [I], V{:X}", x),
+        LdRegsMem(x) => format!("LD V{:X}, [I]", x),
+        // opcodes unknown to the CPU are shown as raw data
+        Unknown(value) => format!("DW {:#06x}", value),
+    }
+}
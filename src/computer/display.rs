@@ -1,16 +1,57 @@
+// Size of the CHIP-8 screen, displays start with it
 pub const WIDTH: u8 = 64;
 pub const HEIGHT: u8 = 32;
+// SCHIP high resolution mode
+pub const HIRES_WIDTH: u8 = 128;
+pub const HIRES_HEIGHT: u8 = 64;
 
 pub struct Display {
-    pub memory: [u8; 2048],
+    // One byte per pixel, row by row
+    pub memory: Vec<u8>,
+    width: usize,
+    height: usize,
 }
 
 impl Display {
     pub fn new() -> Display {
-        Display { memory: [0; 2048] }
+        Display::with_size(WIDTH as usize, HEIGHT as usize)
+    }
+
+    pub fn with_size(width: usize, height: usize) -> Display {
+        Display { memory: vec![0; width * height], width, height }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Switches the resolution, e.g. between SCHIP's low and high resolution modes;
+    // the screen is cleared, frontends follow the new size on their next frame
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Display::with_size(width, height);
     }
 
     pub fn reset(&mut self) {
         self.memory.fill(0);
     }
-}
\ No newline at end of file
+
+    // One text line per row, lit pixels are `#`
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity(self.memory.len() + self.height);
+        for row in self.memory.chunks(self.width) {
+            ascii.extend(row.iter().map(|pixel| if *pixel != 0 { '#' } else { '.' }));
+            ascii.push('\n');
+        }
+        ascii
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
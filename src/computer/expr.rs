@@ -0,0 +1,395 @@
+// Expression language for conditional breakpoints, e.g. `pc == 0x2A4 && v3 > 10`
+//
+// Values: decimal and hex (0x) numbers, registers v0..vf, pc, i, sp,
+// dt (delay timer), st (sound timer), hits (breakpoint hit count)
+// and memory bytes `[i + 2]`. Operators follow C precedence,
+// comparisons and logical operators evaluate to 1 or 0.
+
+use crate::computer::cpu::CPU;
+
+// Machine state visible to expressions
+#[derive(Clone, Copy)]
+pub struct State<'a> {
+    pub cpu: &'a CPU,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub hits: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var {
+    Pc,
+    I,
+    Sp,
+    Dt,
+    St,
+    Hits,
+    V(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Var(Var),
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+// Binary operators grouped by precedence, lowest first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+// Longest operators go first, so `<=` isn't tokenized as `<`
+const OPERATORS: [&str; 24] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "*", "/", "%", "!", "~", "(", ")", "[", "]",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.parse_binary(0)?;
+
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {token:?} in expression: {source}")),
+        }
+    }
+
+    pub fn eval(&self, state: &State) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Var(var) => match var {
+                Var::Pc => state.cpu.pc as i64,
+                Var::I => state.cpu.i_reg as i64,
+                Var::Sp => state.cpu.sp as i64,
+                Var::Dt => state.delay_timer as i64,
+                Var::St => state.sound_timer as i64,
+                Var::Hits => state.hits as i64,
+                Var::V(index) => state.cpu.regs[*index as usize] as i64,
+            },
+            // reads memory directly, so watchpoints aren't triggered
+            Expr::Memory(addr) => {
+                let addr = addr.eval(state);
+                usize::try_from(addr)
+                    .ok()
+                    .and_then(|addr| state.cpu.bus.memory.get(addr))
+                    .map_or(0, |value| *value as i64)
+            },
+            Expr::Unary(op, expr) => {
+                let value = expr.eval(state);
+                match op {
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::BitNot => !value,
+                }
+            },
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(state);
+                // short-circuit logical operators
+                match op {
+                    BinaryOp::And if left == 0 => return 0,
+                    BinaryOp::Or if left != 0 => return 1,
+                    _ => {}
+                }
+                let right = right.eval(state);
+
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (right != 0) as i64,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::Eq => (left == right) as i64,
+                    BinaryOp::Ne => (left != right) as i64,
+                    BinaryOp::Lt => (left < right) as i64,
+                    BinaryOp::Le => (left <= right) as i64,
+                    BinaryOp::Gt => (left > right) as i64,
+                    BinaryOp::Ge => (left >= right) as i64,
+                    BinaryOp::Shl => left.wrapping_shl(right as u32),
+                    BinaryOp::Shr => left.wrapping_shr(right as u32),
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    // division by zero evaluates to 0 instead of crashing the emulator
+                    BinaryOp::Div => left.checked_div(right).unwrap_or(0),
+                    BinaryOp::Rem => left.checked_rem(right).unwrap_or(0),
+                }
+            },
+        }
+    }
+
+    pub fn is_true(&self, state: &State) -> bool {
+        self.eval(state) != 0
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(value)) if value == op => Ok(()),
+            Some(token) => Err(format!("Expected `{op}`, found {token:?}")),
+            None => Err(format!("Expected `{op}`, found end of expression")),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(value)) => PRECEDENCE[level]
+                    .iter()
+                    .find(|(symbol, _)| symbol == value)
+                    .map(|(_, op)| *op),
+                _ => None,
+            };
+
+            match op {
+                Some(op) => {
+                    self.position += 1;
+                    let right = self.parse_binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                },
+                None => return Ok(left),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek() {
+            Some(Token::Op("!")) => Some(UnaryOp::Not),
+            Some(Token::Op("-")) => Some(UnaryOp::Neg),
+            Some(Token::Op("~")) => Some(UnaryOp::BitNot),
+            _ => None,
+        };
+
+        match op {
+            Some(op) => {
+                self.position += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            },
+            None => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Ident(name)) => parse_var(&name).map(Expr::Var),
+            Some(Token::Op("(")) => {
+                let expr = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            Some(Token::Op("[")) => {
+                let expr = self.parse_binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            },
+            Some(token) => Err(format!("Unexpected {token:?} in expression")),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+}
+
+fn parse_var(name: &str) -> Result<Var, String> {
+    let var = match name.to_ascii_lowercase().as_str() {
+        "pc" => Var::Pc,
+        "i" => Var::I,
+        "sp" => Var::Sp,
+        "dt" => Var::Dt,
+        "st" => Var::St,
+        "hits" => Var::Hits,
+        reg => {
+            let index = reg
+                .strip_prefix('v')
+                .filter(|index| index.len() == 1)
+                .and_then(|index| u8::from_str_radix(index, 16).ok())
+                .ok_or(format!("Unknown variable: {name}"))?;
+            Var::V(index)
+        },
+    };
+
+    Ok(var)
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap();
+
+        if first.is_ascii_alphanumeric() || first == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+
+            let token = if first.is_ascii_digit() {
+                Token::Number(parse_number(word)?)
+            } else {
+                Token::Ident(word.to_string())
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or(format!("Unexpected character `{first}` in expression: {source}"))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<i64, String> {
+    let parsed = match word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => word.parse::<i64>(),
+    };
+
+    parsed.map_err(|_| format!("Invalid number: {word}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_with(source: &str, cpu: &CPU) -> i64 {
+        let state = State { cpu, delay_timer: 5, sound_timer: 6, hits: 3 };
+        Expr::parse(source).unwrap().eval(&state)
+    }
+
+    fn eval(source: &str) -> i64 {
+        eval_with(source, &CPU::new())
+    }
+
+    #[test]
+    fn parses_numbers_and_variables() {
+        assert_eq!(Expr::parse("0x2A4").unwrap(), Expr::Number(0x2A4));
+        assert_eq!(Expr::parse("VF").unwrap(), Expr::Var(Var::V(0xF)));
+        assert_eq!(
+            Expr::parse("[i + 2]").unwrap(),
+            Expr::Memory(Box::new(Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Var(Var::I)),
+                Box::new(Expr::Number(2))
+            )))
+        );
+    }
+
+    #[test]
+    fn follows_c_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("6 & 3 == 3"), 0);
+        assert_eq!(eval("1 | 2 ^ 3 & 1"), 3);
+        assert_eq!(eval("1 || 0 && 0"), 1);
+        assert_eq!(eval("-2 * -3"), 6);
+        assert_eq!(eval("!0 + ~0"), 0);
+        assert_eq!(eval("2 < 3 == 1"), 1);
+    }
+
+    #[test]
+    fn evaluates_machine_state() {
+        let mut cpu = CPU::new();
+        cpu.regs[3] = 11;
+        cpu.i_reg = 0x300;
+        cpu.bus.memory[0x302] = 0xAB;
+
+        assert_eq!(eval_with("v3 > 10 && hits == 3", &cpu), 1);
+        assert_eq!(eval_with("[i + 2]", &cpu), 0xAB);
+        assert_eq!(eval_with("dt + st", &cpu), 11);
+        assert_eq!(eval_with("pc", &cpu), cpu.pc as i64);
+        // out of memory reads and division by zero evaluate to 0
+        assert_eq!(eval_with("[0x1000] + [-1] + v3 / 0 + v3 % 0", &cpu), 0);
+    }
+
+    #[test]
+    fn short_circuits_logical_operators() {
+        assert_eq!(eval("0 && 1 / 0 == 0"), 0);
+        assert_eq!(eval("5 || 0"), 1);
+        assert_eq!(eval("5 && 7"), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for source in ["", "v3 >", "(1 + 2", "[i", "vg == 1", "foo", "1 $ 2", "0xZZ", "1 2", "v10"] {
+            assert!(Expr::parse(source).is_err(), "{source} was accepted");
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::computer::Computer;
+use crate::computer::bus::MEMORY_SIZE;
+use crate::computer::disasm::disassemble;
+use crate::computer::opcode::Opcode;
+
+// Executed instructions kept for crash reports
+pub const HISTORY_SIZE: usize = 32;
+// Bytes shown before and after an address in hexdumps
+const HEXDUMP_CONTEXT: usize = 32;
+const HEXDUMP_ROW: usize = 16;
+
+// Error which stops the emulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    UnknownOpcode(u16),
+    // Call with all 16 stack entries in use
+    StackOverflow,
+    // Return with an empty stack
+    StackUnderflow,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::UnknownOpcode(opcode) => write!(f, "unknown opcode {:#06x}", opcode),
+            Fault::StackOverflow => write!(f, "stack overflow"),
+            Fault::StackUnderflow => write!(f, "return with empty stack"),
+        }
+    }
+}
+
+// Recently executed instructions as (PC, opcode), oldest first
+pub struct History {
+    entries: VecDeque<(u16, u16)>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { entries: VecDeque::with_capacity(HISTORY_SIZE) }
+    }
+
+    pub fn push(&mut self, pc: u16, opcode: u16) {
+        if self.entries.len() == HISTORY_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((pc, opcode));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &(u16, u16)> {
+        self.entries.iter()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+// Human-readable machine state at the moment of the fault
+pub fn crash_report(computer: &Computer, fault: &Fault) -> String {
+    let cpu = &computer.cpu;
+    let memory = &cpu.bus.memory;
+    let opcode = match memory.get(cpu.pc..cpu.pc + 2) {
+        Some(bytes) => Opcode::from(bytes[0], bytes[1]),
+        None => Opcode::new(0),
+    };
+
+    let mut report = String::from("=== CRAB8 crash report ===\n");
+    report.push_str(&format!("Fault: {}\n", fault));
+    report.push_str(&format!("PC: {:#05x}  opcode: {:04x}  {}\n", cpu.pc, opcode.value(), disassemble(&opcode)));
+    report.push_str(&format!("Cycle: {}\n", computer.cycles));
+
+    report.push_str("\nRegisters:\n");
+    for (row_index, values) in cpu.regs.chunks(8).enumerate() {
+        let line: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("V{:X}={:02x}", row_index * 8 + index, value))
+            .collect();
+        report.push_str(&format!("  {}\n", line.join(" ")));
+    }
+    report.push_str(&format!(
+        "  I={:#05x} SP={} DT={:02x} ST={:02x}\n",
+        cpu.i_reg, cpu.sp, computer.delay_timer, computer.sound_timer
+    ));
+
+    report.push_str("\nCall stack (innermost first):\n");
+    let call_stack = cpu.call_stack();
+    if call_stack.is_empty() {
+        report.push_str("  (empty)\n");
+    }
+    for (depth, addr) in call_stack.iter().rev().enumerate() {
+        report.push_str(&format!("  #{} return to {:#05x}\n", depth, addr));
+    }
+
+    report.push_str(&format!("\nMemory around PC ({:#05x}):\n", cpu.pc));
+    report.push_str(&hexdump(memory, cpu.pc));
+    report.push_str(&format!("\nMemory around I ({:#05x}):\n", cpu.i_reg));
+    report.push_str(&hexdump(memory, cpu.i_reg as usize));
+
+    report.push_str("\nLast executed instructions (oldest first):\n");
+    for (pc, opcode) in computer.history.entries() {
+        let opcode = Opcode::new(*opcode);
+        report.push_str(&format!("  {:03x}: {:04x}  {}\n", pc, opcode.value(), disassemble(&opcode)));
+    }
+
+    report.push_str("\nScreen:\n");
+    report.push_str(&computer.display.to_ascii());
+
+    report
+}
+
+// Rows of 16 bytes around `addr`, the byte at `addr` is put in brackets
+fn hexdump(memory: &[u8], addr: usize) -> String {
+    let start = addr.saturating_sub(HEXDUMP_CONTEXT) / HEXDUMP_ROW * HEXDUMP_ROW;
+    let end = (addr + HEXDUMP_CONTEXT).min(MEMORY_SIZE);
+    let mut dump = String::new();
+
+    for row_addr in (start..end).step_by(HEXDUMP_ROW) {
+        dump.push_str(&format!("  {:03x}:", row_addr));
+        let row_end = (row_addr + HEXDUMP_ROW).min(MEMORY_SIZE);
+        for (byte_addr, value) in memory[row_addr..row_end].iter().enumerate() {
+            let byte_addr = row_addr + byte_addr;
+            if byte_addr == addr {
+                dump.push_str(&format!("[{:02x}]", value));
+            } else if byte_addr == addr + 1 {
+                dump.push_str(&format!("{:02x}", value));
+            } else {
+                dump.push_str(&format!(" {:02x}", value));
+            }
+        }
+        dump.push('\n');
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Status;
+
+    // Runs `rom` until it faults
+    fn crash(rom: Vec<u8>) -> (Computer, Fault) {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        for _ in 0..64 {
+            if let Status::Fault(fault) = computer.step() {
+                return (computer, fault);
+            }
+        }
+        panic!("no fault");
+    }
+
+    #[test]
+    fn history_keeps_the_last_instructions() {
+        let mut history = History::new();
+        for pc in 0..HISTORY_SIZE as u16 + 2 {
+            history.push(pc, 0x1000 + pc);
+        }
+        let entries: Vec<&(u16, u16)> = history.entries().collect();
+        assert_eq!(entries.len(), HISTORY_SIZE);
+        assert_eq!(*entries[0], (2, 0x1002));
+        assert_eq!(*entries[HISTORY_SIZE - 1], (HISTORY_SIZE as u16 + 1, 0x1000 + HISTORY_SIZE as u16 + 1));
+
+        history.clear();
+        assert_eq!(history.entries().count(), 0);
+    }
+
+    #[test]
+    fn reports_stack_underflow() {
+        let (computer, fault) = crash(crate::chip8! {
+            ld v0, 1;
+            ret
+        });
+        assert_eq!(fault, Fault::StackUnderflow);
+
+        // PC stays at the faulting instruction
+        let report = crash_report(&computer, &fault);
+        assert!(report.starts_with("=== CRAB8 crash report ===\nFault: return with empty stack\nPC: 0x202  opcode: 00ee  RET\n"));
+        assert!(report.contains("  V0=01 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00\n"));
+        assert!(report.contains("Call stack (innermost first):\n  (empty)\n"));
+        assert!(report.contains("  200: 60 01[00]ee 00 00"));
+        assert!(report.contains("Last executed instructions (oldest first):\n  200: 6001  LD V0, 0x01\n  202: 00ee  RET\n"));
+        assert!(report.ends_with(&computer.display.to_ascii()));
+    }
+
+    #[test]
+    fn reports_stack_overflow_with_call_stack() {
+        let (computer, fault) = crash(crate::chip8! {
+            start: call start
+        });
+        assert_eq!(fault, Fault::StackOverflow);
+
+        let report = crash_report(&computer, &fault);
+        assert!(report.contains("Fault: stack overflow\nPC: 0x200  opcode: 2200  CALL 0x200\n"));
+        assert!(report.contains("SP=16"));
+        assert!(report.contains("  #0 return to 0x202\n"));
+        assert!(report.contains("  #15 return to 0x202\n"));
+        assert!(!report.contains("#16"));
+    }
+
+    #[test]
+    fn hexdump_marks_the_address() {
+        let mut memory = vec![0; MEMORY_SIZE];
+        memory[0x210] = 0xab;
+        memory[0x211] = 0xcd;
+
+        let dump = hexdump(&memory, 0x210);
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[0].starts_with("  1f0: 00"));
+        assert_eq!(rows[2], "  210:[ab]cd 00 00 00 00 00 00 00 00 00 00 00 00 00 00");
+
+        // the dump is clamped to the memory
+        assert!(hexdump(&memory, 0).starts_with("  000:[00]"));
+        assert_eq!(hexdump(&memory, MEMORY_SIZE - 1).lines().last(), Some("  ff0: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00[00]"));
+    }
+}
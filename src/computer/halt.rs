@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::computer::bus::{Access, AccessKind};
+use crate::computer::cpu::CPU;
+use crate::computer::instruction::{decode, Instruction};
+
+// Longest loop (in instructions) found by the loop detection
+const MAX_LOOP_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HaltReason {
+    // `1nnn` jumping to itself
+    SelfJump(u16),
+    // Instructions repeating without changing any state
+    Loop { pc: u16, length: usize },
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltReason::SelfJump(pc) => write!(f, "jump to itself at {:#05x}", pc),
+            HaltReason::Loop { pc, length } => {
+                write!(f, "loop of {} instructions at {:#05x} without state changes", length, pc)
+            },
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct Snapshot {
+    pc: usize,
+    regs: [u8; 16],
+    i_reg: u16,
+    sp: usize,
+    stack: [u16; 16],
+}
+
+pub struct HaltDetector {
+    // Also look for short loops without state changes, not only self-jumps
+    pub detect_loops: bool,
+    // States after recent side-effect free instructions
+    history: VecDeque<Snapshot>,
+}
+
+impl HaltDetector {
+    pub fn new(detect_loops: bool) -> HaltDetector {
+        HaltDetector { detect_loops, history: VecDeque::with_capacity(MAX_LOOP_LENGTH) }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    // Checks instruction executed at `pc`, CPU state is the one after its execution
+    pub fn check(&mut self, pc: usize, cpu: &CPU, accesses: &[Access]) -> Option<HaltReason> {
+        let instruction = decode(&cpu.opcode);
+        if instruction == Instruction::Jp(pc as u16) {
+            return Some(HaltReason::SelfJump(pc as u16));
+        }
+
+        if !self.detect_loops {
+            return None;
+        }
+
+        // instructions depending on input, timers or randomness may break the loop later,
+        // and writes change the state, so the history starts over
+        let has_writes = accesses.iter().any(|access| access.kind == AccessKind::Write);
+        if has_writes || has_side_effects(&instruction) {
+            self.history.clear();
+            return None;
+        }
+
+        let snapshot = Snapshot {
+            pc: cpu.pc,
+            regs: cpu.regs,
+            i_reg: cpu.i_reg,
+            sp: cpu.sp,
+            stack: cpu.stack,
+        };
+
+        if let Some(position) = self.history.iter().position(|previous| *previous == snapshot) {
+            let length = self.history.len() - position;
+            return Some(HaltReason::Loop { pc: cpu.pc as u16, length });
+        }
+
+        if self.history.len() == MAX_LOOP_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+
+        None
+    }
+}
+
+fn has_side_effects(instruction: &Instruction) -> bool {
+    use Instruction::*;
+
+    // clearing the screen or switching its resolution, random numbers, drawing, keys and timers
+    matches!(
+        instruction,
+        Cls | Low | High | Rnd(..) | Drw(..) | Skp(_) | Sknp(_) | LdRegDt(_) | LdRegK(_) | LdDtReg(_) | LdStReg(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::{Computer, Status};
+
+    // Runs `rom` until it stops running or `steps` instructions were executed
+    fn run(rom: Vec<u8>, detect_loops: bool, steps: usize) -> Status {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer.halt_detector.detect_loops = detect_loops;
+        let mut status = Status::Running;
+        for _ in 0..steps {
+            status = computer.step();
+            if status != Status::Running {
+                break;
+            }
+        }
+        status
+    }
+
+    #[test]
+    fn halts_on_self_jump() {
+        let rom = crate::chip8! {
+            ld v0, 1;
+            end: jp end
+        };
+        assert_eq!(run(rom, false, 100), Status::Halted(HaltReason::SelfJump(0x202)));
+        assert_eq!(HaltReason::SelfJump(0x202).to_string(), "jump to itself at 0x202");
+    }
+
+    #[test]
+    fn detects_loops_only_when_enabled() {
+        let rom = crate::chip8! {
+            start: ld v0, 1;
+            jp start
+        };
+        assert_eq!(run(rom.clone(), false, 100), Status::Running);
+
+        // the state after the third instruction is the same as after the first one
+        let status = run(rom, true, 100);
+        assert_eq!(status, Status::Halted(HaltReason::Loop { pc: 0x202, length: 2 }));
+        assert_eq!(
+            HaltReason::Loop { pc: 0x202, length: 2 }.to_string(),
+            "loop of 2 instructions at 0x202 without state changes"
+        );
+    }
+
+    #[test]
+    fn loops_changing_registers_keep_running() {
+        let rom = crate::chip8! {
+            start: add v0, 1;
+            jp start
+        };
+        assert_eq!(run(rom, true, 200), Status::Running);
+    }
+
+    #[test]
+    fn memory_writes_reset_the_history() {
+        let rom = crate::chip8! {
+            ld i, 0x300;
+            start: ld [i], v0;
+            jp start
+        };
+        assert_eq!(run(rom, true, 200), Status::Running);
+    }
+
+    #[test]
+    fn side_effects_reset_the_history() {
+        // the delay timer may break the loop later
+        let rom = crate::chip8! {
+            start: ld v0, dt;
+            jp start
+        };
+        assert_eq!(run(rom, true, 200), Status::Running);
+
+        let rom = crate::chip8! {
+            start: skp v0;
+            jp start
+        };
+        assert_eq!(run(rom, true, 200), Status::Running);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::computer::opcode::Opcode;
+
+// Index of a V register, 0x0..=0xF
+pub type Reg = u8;
+
+// Decoded instruction, names follow Cowgod's notation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 00FE, SCHIP low resolution (64x32)
+    Low,
+    // 00FF, SCHIP high resolution (128x64)
+    High,
+    // 0nnn, machine code routine, not supported
+    Sys(u16),
+    // 1nnn
+    Jp(u16),
+    // 2nnn
+    Call(u16),
+    // 3xkk
+    SeByte(Reg, u8),
+    // 4xkk
+    SneByte(Reg, u8),
+    // 5xy0
+    SeReg(Reg, Reg),
+    // 6xkk
+    LdByte(Reg, u8),
+    // 7xkk
+    AddByte(Reg, u8),
+    // 8xy0
+    LdReg(Reg, Reg),
+    // 8xy1
+    Or(Reg, Reg),
+    // 8xy2
+    And(Reg, Reg),
+    // 8xy3
+    Xor(Reg, Reg),
+    // 8xy4
+    AddReg(Reg, Reg),
+    // 8xy5
+    Sub(Reg, Reg),
+    // 8xy6
+    Shr(Reg, Reg),
+    // 8xy7
+    Subn(Reg, Reg),
+    // 8xyE
+    Shl(Reg, Reg),
+    // 9xy0
+    SneReg(Reg, Reg),
+    // Annn
+    LdI(u16),
+    // Bnnn
+    JpV0(u16),
+    // Cxkk
+    Rnd(Reg, u8),
+    // Dxyn
+    Drw(Reg, Reg, u8),
+    // Ex9E
+    Skp(Reg),
+    // ExA1
+    Sknp(Reg),
+    // Fx07
+    LdRegDt(Reg),
+    // Fx0A
+    LdRegK(Reg),
+    // Fx15
+    LdDtReg(Reg),
+    // Fx18
+    LdStReg(Reg),
+    // Fx1E
+    AddIReg(Reg),
+    // Fx29
+    LdFReg(Reg),
+    // Fx33
+    LdBReg(Reg),
+    // Fx55
+    LdMemRegs(Reg),
+    // Fx65
+    LdRegsMem(Reg),
+    // Any opcode not listed above
+    Unknown(u16),
+}
+
+pub fn decode(opcode: &Opcode) -> Instruction {
+    use Instruction::*;
+
+    let x = opcode.get_x();
+    let y = opcode.get_y();
+    let n = opcode.get_z();
+    let nn = opcode.get_nn();
+    let nnn = opcode.get_nnn();
+
+    match opcode.value() & 0xF000 {
+        0x0000 => match opcode.value() {
+            0x00E0 => Cls,
+            0x00EE => Ret,
+            0x00FE => Low,
+            0x00FF => High,
+            _ => Sys(nnn),
+        },
+        0x1000 => Jp(nnn),
+        0x2000 => Call(nnn),
+        0x3000 => SeByte(x, nn),
+        0x4000 => SneByte(x, nn),
+        0x5000 if n == 0 => SeReg(x, y),
+        0x6000 => LdByte(x, nn),
+        0x7000 => AddByte(x, nn),
+        0x8000 => match n {
+            0x0 => LdReg(x, y),
+            0x1 => Or(x, y),
+            0x2 => And(x, y),
+            0x3 => Xor(x, y),
+            0x4 => AddReg(x, y),
+            0x5 => Sub(x, y),
+            0x6 => Shr(x, y),
+            0x7 => Subn(x, y),
+            0xE => Shl(x, y),
+            _ => Unknown(opcode.value()),
+        },
+        0x9000 if n == 0 => SneReg(x, y),
+        0xA000 => LdI(nnn),
+        0xB000 => JpV0(nnn),
+        0xC000 => Rnd(x, nn),
+        0xD000 => Drw(x, y, n),
+        0xE000 => match nn {
+            0x9E => Skp(x),
+            0xA1 => Sknp(x),
+            _ => Unknown(opcode.value()),
+        },
+        0xF000 => match nn {
+            0x07 => LdRegDt(x),
+            0x0A => LdRegK(x),
+            0x15 => LdDtReg(x),
+            0x18 => LdStReg(x),
+            0x1E => AddIReg(x),
+            0x29 => LdFReg(x),
+            0x33 => LdBReg(x),
+            0x55 => LdMemRegs(x),
+            0x65 => LdRegsMem(x),
+            _ => Unknown(opcode.value()),
+        },
+        _ => Unknown(opcode.value()),
+    }
+}
+
+// const, so `chip8!` programs are assembled at compile time
+pub const fn encode(instruction: &Instruction) -> Opcode {
+    use Instruction::*;
+
+    const fn x(x: Reg) -> u16 {
+        (x as u16 & 0xF) << 8
+    }
+    const fn xy(x_reg: Reg, y: Reg) -> u16 {
+        x(x_reg) | (y as u16 & 0xF) << 4
+    }
+    const fn nnn(addr: u16) -> u16 {
+        addr & 0xFFF
+    }
+
+    let value = match *instruction {
+        Cls => 0x00E0,
+        Ret => 0x00EE,
+        Low => 0x00FE,
+        High => 0x00FF,
+        Sys(addr) => nnn(addr),
+        Jp(addr) => 0x1000 | nnn(addr),
+        Call(addr) => 0x2000 | nnn(addr),
+        SeByte(vx, byte) => 0x3000 | x(vx) | byte as u16,
+        SneByte(vx, byte) => 0x4000 | x(vx) | byte as u16,
+        SeReg(vx, vy) => 0x5000 | xy(vx, vy),
+        LdByte(vx, byte) => 0x6000 | x(vx) | byte as u16,
+        AddByte(vx, byte) => 0x7000 | x(vx) | byte as u16,
+        LdReg(vx, vy) => 0x8000 | xy(vx, vy),
+        Or(vx, vy) => 0x8001 | xy(vx, vy),
+        And(vx, vy) => 0x8002 | xy(vx, vy),
+        Xor(vx, vy) => 0x8003 | xy(vx, vy),
+        AddReg(vx, vy) => 0x8004 | xy(vx, vy),
+        Sub(vx, vy) => 0x8005 | xy(vx, vy),
+        Shr(vx, vy) => 0x8006 | xy(vx, vy),
+        Subn(vx, vy) => 0x8007 | xy(vx, vy),
+        Shl(vx, vy) => 0x800E | xy(vx, vy),
+        SneReg(vx, vy) => 0x9000 | xy(vx, vy),
+        LdI(addr) => 0xA000 | nnn(addr),
+        JpV0(addr) => 0xB000 | nnn(addr),
+        Rnd(vx, byte) => 0xC000 | x(vx) | byte as u16,
+        Drw(vx, vy, height) => 0xD000 | xy(vx, vy) | (height as u16 & 0xF),
+        Skp(vx) => 0xE09E | x(vx),
+        Sknp(vx) => 0xE0A1 | x(vx),
+        LdRegDt(vx) => 0xF007 | x(vx),
+        LdRegK(vx) => 0xF00A | x(vx),
+        LdDtReg(vx) => 0xF015 | x(vx),
+        LdStReg(vx) => 0xF018 | x(vx),
+        AddIReg(vx) => 0xF01E | x(vx),
+        LdFReg(vx) => 0xF029 | x(vx),
+        LdBReg(vx) => 0xF033 | x(vx),
+        LdMemRegs(vx) => 0xF055 | x(vx),
+        LdRegsMem(vx) => 0xF065 | x(vx),
+        Unknown(value) => value,
+    };
+
+    Opcode::new(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_opcode() {
+        for value in 0..=u16::MAX {
+            let instruction = decode(&Opcode::new(value));
+            assert_eq!(encode(&instruction).value(), value, "{instruction:?}");
+        }
+    }
+
+    #[test]
+    fn decodes_operands() {
+        assert_eq!(decode(&Opcode::new(0xD12F)), Instruction::Drw(1, 2, 0xF));
+        assert_eq!(decode(&Opcode::new(0x6AFF)), Instruction::LdByte(0xA, 0xFF));
+        assert_eq!(decode(&Opcode::new(0x5121)), Instruction::Unknown(0x5121));
+    }
+}
@@ -36,12 +36,21 @@ impl Keyboard {
         Keyboard { keys: [false; 16], key_index_map: HashMap::from(key_indexes) }
     }
 
+    // Returns keypad key mapped to the keycode
+    pub fn key_index(&self, keycode: Keycode) -> Option<usize> {
+        self.key_index_map.get(&keycode).copied()
+    }
+
     pub fn register_key_event(&mut self, keycode: Keycode, is_key_press: bool) {
-        let key_index = match self.key_index_map.get(&keycode) {
-            Some(value) => *value,
+        let key_index = match self.key_index(keycode) {
+            Some(value) => value,
             None => return
         };
 
         self.keys[key_index] = is_key_press;
     }
+
+    pub fn set_key(&mut self, key_index: usize, is_key_press: bool) {
+        self.keys[key_index] = is_key_press;
+    }
 }
\ No newline at end of file
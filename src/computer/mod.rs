@@ -1,17 +1,64 @@
+pub mod asm;
+pub mod bus;
+pub mod coverage;
 pub mod cpu;
+pub mod debugger;
+pub mod disasm;
 pub mod display;
+pub mod expr;
+pub mod fault;
+pub mod halt;
+pub mod instruction;
 pub mod opcode;
+pub mod profiler;
+pub mod quirks;
+pub mod sanitizer;
+pub mod smc;
+pub mod trace;
 pub mod keyboard;
 
 use core::fmt;
+use bus::AccessKind;
+use coverage::Coverage;
 use cpu::CPU;
-use display::Display;
+use debugger::Debugger;
+use display::{Display, HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+use expr::State;
+use fault::{Fault, History};
+use halt::{HaltDetector, HaltReason};
 use crate::utils::FONT;
 
+use self::bus::MEMORY_SIZE;
+use self::instruction::{decode, Instruction, Reg};
 use self::opcode::Opcode;
 use self::keyboard::Keyboard;
+use self::profiler::Profiler;
+use self::sanitizer::Sanitizer;
+use self::smc::SmcDetector;
+use self::trace::{TraceRecord, Tracer};
 
 pub const PROGRAM_START_ADDR: usize = 0x200;
+// Largest ROM fitting into memory after the interpreter area
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START_ADDR;
+// Instructions per second
+pub const CLOCK_SPEED: u32 = 2564;
+// Instructions between two 60Hz timer ticks
+pub const CYCLES_PER_FRAME: u32 = CLOCK_SPEED / 60;
+
+// Result of a single `Computer::step`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    // Instruction was executed
+    Running,
+    // Waiting for a key press (Fx0A)
+    WaitingKey,
+    // Stopped by the debugger
+    Paused,
+    // Program reached an infinite loop and won't make progress
+    Halted(HaltReason),
+    // Emulation stopped on an error
+    Fault(Fault),
+}
 
 pub struct Computer {
     pub cpu: CPU,
@@ -21,6 +68,8 @@ pub struct Computer {
     pub keyboard: Keyboard,
     // Wait-key flag
     pub waiting_key: bool,
+    // Set by Dxyn with the display wait quirk until the next timer tick
+    pub waiting_vblank: bool,
     // Drawing flag - if true - SDL drawing occurs
     pub should_redraw: bool,
     // Clear screen flag - if true - SDL will clear screen
@@ -29,6 +78,30 @@ pub struct Computer {
     pub delay_timer: u8,
     // Sound timer
     pub sound_timer: u8,
+    // Pause/step state and breakpoints, paused on watchpoint hits
+    pub debugger: Debugger,
+    // Number of executed instructions
+    pub cycles: u64,
+    // Execution trace writer, enabled with --trace
+    pub tracer: Option<Tracer>,
+    // Instruction profiler, enabled with --profile
+    pub profiler: Option<Profiler>,
+    // ROM code coverage, enabled with --coverage
+    pub coverage: Option<Coverage>,
+    // Uninitialized read detection, enabled with --sanitize
+    pub sanitizer: Option<Sanitizer>,
+    // Self-modifying code detection, enabled with --smc
+    pub smc: Option<SmcDetector>,
+    // Infinite loop detection
+    pub halt_detector: HaltDetector,
+    // Set once the program is halted
+    pub halted: Option<HaltReason>,
+    // Set once the emulation stops on an error
+    pub fault: Option<Fault>,
+    // Recently executed instructions for crash reports
+    pub history: History,
+    // Messages of the debugger and analysis tools, until the frontend takes them
+    pub messages: Vec<String>,
 }
 
 impl Computer {
@@ -39,121 +112,237 @@ impl Computer {
             keyboard: Keyboard::new(),
 
             waiting_key: false,
+            waiting_vblank: false,
             should_redraw: false,
             should_clear_screen: false,
             delay_timer: 0,
             sound_timer: 0,
+            debugger: Debugger::new(),
+            cycles: 0,
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            sanitizer: None,
+            smc: None,
+            halt_detector: HaltDetector::new(false),
+            halted: None,
+            fault: None,
+            history: History::new(),
+            messages: Vec::new(),
         }
     }
 
     pub fn reset(&mut self) {
         self.cpu.reset();
-        self.display.reset();
+        self.display.resize(WIDTH as usize, HEIGHT as usize);
         self.waiting_key = false;
+        self.waiting_vblank = false;
         self.should_redraw = false;
         self.should_clear_screen = false;
         self.delay_timer = 0;
+        self.cycles = 0;
+        self.halt_detector.reset();
+        self.halted = None;
+        self.fault = None;
+        self.history.clear();
         
         self.load_font();
     }
 
-    pub fn load_rom(&mut self, rom_data: Vec<u8>) {
+    pub fn load_rom(&mut self, rom_data: Vec<u8>) -> Result<(), String> {
+        if rom_data.len() > MAX_ROM_SIZE {
+            return Err(format!("ROM is too large: {} bytes, at most {MAX_ROM_SIZE} fit into memory", rom_data.len()));
+        }
         let end_addr = PROGRAM_START_ADDR + rom_data.len();
-        self.cpu.memory[PROGRAM_START_ADDR..end_addr].copy_from_slice(rom_data.as_slice());
+        self.cpu.bus.memory[PROGRAM_START_ADDR..end_addr].copy_from_slice(rom_data.as_slice());
+        Ok(())
     }
 
     pub fn register_key_event(&mut self, keycode: sdl2::keyboard::Keycode, is_key_press: bool) {
-        self.keyboard.register_key_event(keycode, is_key_press);
-        self.waiting_key = false;
+        if let Some(key_index) = self.keyboard.key_index(keycode) {
+            self.set_key(key_index, is_key_press);
+        }
+    }
+
+    // Presses or releases hex keypad key 0x0..=0xF,
+    // Fx0A gets the key once it's released, like on the original interpreter
+    pub fn set_key(&mut self, key_index: usize, is_key_press: bool) {
+        let was_pressed = self.keyboard.keys[key_index];
+        self.keyboard.set_key(key_index, is_key_press);
+
+        if let (true, Instruction::LdRegK(x)) = (self.waiting_key, decode(&self.cpu.opcode)) {
+            if was_pressed && !is_key_press {
+                self.cpu.set_reg(x, key_index as u8);
+                self.waiting_key = false;
+            }
+        }
+    }
+
+    // Executes next instruction unless the computer waits for a key, is paused or halted
+    pub fn step(&mut self) -> Status {
+        if let Some(fault) = self.fault {
+            return Status::Fault(fault);
+        }
+        if let Some(reason) = self.halted {
+            return Status::Halted(reason);
+        }
+        if self.waiting_key {
+            return Status::WaitingKey;
+        }
+        // drawing with the display wait quirk ends the frame
+        if self.waiting_vblank {
+            return Status::Running;
+        }
+        // breakpoints stop before the instruction at PC runs
+        if !self.debugger.paused {
+            self.check_breakpoints();
+        }
+        if !self.debugger.should_run() {
+            return Status::Paused;
+        }
+
+        if let Err(fault) = self.emulate_cycle() {
+            self.fault = Some(fault);
+            return Status::Fault(fault);
+        }
+
+        match self.halted {
+            Some(reason) => Status::Halted(reason),
+            None => Status::Running,
+        }
     }
 
-    pub fn emulate_cycle(&mut self) {
+    pub fn emulate_cycle(&mut self) -> Result<(), Fault> {
+        let pc = self.cpu.pc;
+        // memory accesses are only collected for the tools looking at them
+        self.cpu.bus.record_accesses = self.tracer.is_some()
+            || self.coverage.is_some()
+            || self.sanitizer.is_some()
+            || self.smc.is_some()
+            || self.halt_detector.detect_loops;
         let opcode = self.cpu.fetch_opcode();
-        let op_key = opcode.value() & 0xF000;
-
-        match op_key {
-            0 => {
-                let op_key = opcode.get_nn();
-                match op_key {
-                    0xE0 => self.clear_screen(),
-                    0xEE => self.cpu.return_from_subroutine(),
-                    _ => self.unknow_opcode_error(opcode)
-                }
+        self.history.push(pc as u16, opcode.value());
+        match decode(&opcode) {
+            Instruction::Cls => self.clear_screen(),
+            Instruction::Ret => self.cpu.return_from_subroutine()?,
+            Instruction::Low => self.set_resolution(WIDTH, HEIGHT),
+            Instruction::High => self.set_resolution(HIRES_WIDTH, HIRES_HEIGHT),
+            Instruction::Jp(addr) => self.cpu.jump_to_addr(addr),
+            Instruction::Call(addr) => self.cpu.call_at_addr(addr)?,
+            Instruction::SeByte(x, value) => self.cpu.skip_3xkk(x, value),
+            Instruction::SneByte(x, value) => self.cpu.skip_4xkk(x, value),
+            Instruction::SeReg(x, y) => self.cpu.skip_5xy(x, y),
+            Instruction::LdByte(x, value) => self.cpu.put_value_to_vx(x, value),
+            Instruction::AddByte(x, value) => self.cpu.add_value_to_vx(x, value),
+            Instruction::LdReg(x, y) => self.cpu.vy_to_vx(x, y),
+            Instruction::Or(x, y) => self.cpu.vx_or_vy(x, y),
+            Instruction::And(x, y) => self.cpu.vx_and_vy(x, y),
+            Instruction::Xor(x, y) => self.cpu.vx_xor_vy(x, y),
+            Instruction::AddReg(x, y) => self.cpu.vx_add_vy(x, y),
+            Instruction::Sub(x, y) => self.cpu.vx_sub_vy(x, y),
+            Instruction::Shr(x, y) => self.cpu.vx_shr(x, y),
+            Instruction::Subn(x, y) => self.cpu.vy_sub_vx(x, y),
+            Instruction::Shl(x, y) => self.cpu.vx_shl(x, y),
+            Instruction::SneReg(x, y) => self.cpu.skip_9xy(x, y),
+            Instruction::LdI(addr) => self.cpu.set_i_reg(addr),
+            Instruction::JpV0(addr) => self.cpu.jump_to_addr_offset(addr),
+            Instruction::Rnd(x, mask) => self.cpu.add_random_to_vx(x, mask),
+            Instruction::Drw(x, y, height) => self.draw_sprite(x, y, height),
+            Instruction::Skp(x) => self.cpu.skip_on_keydown(&self.keyboard, x),
+            Instruction::Sknp(x) => self.cpu.skip_on_keyup(&self.keyboard, x),
+            Instruction::LdRegDt(x) => {
+                self.cpu.set_reg(x, self.delay_timer);
+                self.cpu.next_instruction();
             },
-            0x1000 => self.cpu.jump_to_addr(),
-            0x2000 => self.cpu.call_at_addr(),
-            0x3000 => self.cpu.skip_3xkk(),
-            0x4000 => self.cpu.skip_4xkk(),
-            0x5000 => self.cpu.skip_5xy(),
-            0x6000 => self.cpu.put_value_to_vx(),
-            0x7000 => self.cpu.add_value_to_vx(),
-            0x8000 => {
-                let op_key = opcode.get_z();
-                match op_key {
-                    0 => self.cpu.vy_to_vx(),
-                    0x1 => self.cpu.vx_or_vy(),
-                    0x2 => self.cpu.vx_and_vy(),
-                    0x3 => self.cpu.vx_xor_vy(),
-                    0x4 => self.cpu.vx_add_vy(),
-                    0x5 => self.cpu.vx_sub_vy(),
-                    0x6 => self.cpu.vx_shr(),
-                    0x7 => self.cpu.vy_sub_vx(),
-                    0xE => self.cpu.vx_shl(),
-                    _ => self.unknow_opcode_error(opcode)
-                }
+            Instruction::LdRegK(_) => {
+                // VX is set by `set_key`, execution stops until then
+                self.waiting_key = true;
+                self.cpu.next_instruction();
             },
-            0x9000 => self.cpu.skip_9xy(),
-            0xA000 => self.cpu.set_i_reg(),
-            0xB000 => self.cpu.jump_to_addr_offset(),
-            0xC000 => self.cpu.add_random_to_vx(),
-            0xD000 => self.draw_sprite(),
-            0xE000 => {
-                let op_key = opcode.get_nn();
-                match op_key {
-                    0x9E => self.cpu.skip_on_keydown(&self.keyboard),
-                    0xA1 => self.cpu.skip_on_keyup(&self.keyboard), 
-                    _ => self.unknow_opcode_error(opcode)
-                }
+            Instruction::LdDtReg(x) => {
+                self.delay_timer = self.cpu.reg(x);
+                self.cpu.next_instruction();
             },
-            0xF000 => {
-                let op_key = opcode.get_nn();
-                match op_key {
-                    0x07 => {
-                        self.cpu.set_vx(self.delay_timer);
-                        self.cpu.next_instruction();
-                    },
-                    0x0A => {
-                        // TODO: stop execution on keypress await
-                        self.waiting_key = true;
-                        self.cpu.next_instruction();
-                    },
-                    0x15 => {
-                        self.delay_timer = self.cpu.get_vx();
-                        self.cpu.next_instruction();
-                    },
-                    0x18 => {
-                        self.sound_timer = self.cpu.get_vx();
-                        self.cpu.next_instruction();
-                    },
-                    0x1E => self.cpu.add_vx_to_i(),
-                    0x29 => self.cpu.set_font_char_addr(),
-                    0x33 => self.cpu.vx_decimal_to_ireg(),
-                    0x55 => self.cpu.store_regs_in_memory(),
-                    0x65 => self.cpu.store_memory_in_regs(),
-                    _ => self.unknow_opcode_error(opcode)
-                }
+            Instruction::LdStReg(x) => {
+                self.sound_timer = self.cpu.reg(x);
+                self.cpu.next_instruction();
             },
-            _ => self.unknow_opcode_error(opcode)
+            Instruction::AddIReg(x) => self.cpu.add_vx_to_i(x),
+            Instruction::LdFReg(x) => self.cpu.set_font_char_addr(x),
+            Instruction::LdBReg(x) => self.cpu.vx_decimal_to_ireg(x),
+            Instruction::LdMemRegs(x) => self.cpu.store_regs_in_memory(x),
+            Instruction::LdRegsMem(x) => self.cpu.store_memory_in_regs(x),
+            Instruction::Sys(_) | Instruction::Unknown(_) => self.unknow_opcode_error(opcode)?,
         };
+        // PC wraps around the 4KB address space like memory accesses
+        self.cpu.pc %= MEMORY_SIZE;
+
+        self.trace(pc);
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc as u16, &self.cpu.opcode, self.cpu.regs[0xF], self.cpu.call_stack());
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(self.cpu.bus.accesses());
+        }
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            let reports = sanitizer.check(
+                pc as u16,
+                &self.cpu.opcode,
+                &self.cpu.quirks,
+                self.cpu.bus.accesses(),
+                self.cpu.call_stack(),
+            );
+            for report in reports {
+                self.messages.push(format!("Sanitizer: {report}"));
+            }
+        }
+        self.check_self_modification(pc);
+        self.halted = self.halt_detector.check(pc, &self.cpu, self.cpu.bus.accesses());
+        self.cycles += 1;
+
+        self.handle_watch_hits();
+
+        Ok(())
+    }
+
+    // Runs one frame worth of instructions and ticks the timers,
+    // returns status of the last step
+    pub fn run_frame(&mut self) -> Status {
+        let mut status = Status::Running;
+        for _ in 0..CYCLES_PER_FRAME {
+            status = self.step();
+            if status != Status::Running || self.waiting_vblank {
+                break;
+            }
+        }
+
+        self.tick_timers();
+        status
+    }
+
+    // Called at 60Hz
+    pub fn tick_timers(&mut self) {
+        self.waiting_vblank = false;
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.end_frame();
+        }
     }
 
     fn load_font(&mut self) {
-        self.cpu.memory[0..FONT.len()].copy_from_slice(&FONT);
+        self.cpu.bus.memory[0..FONT.len()].copy_from_slice(&FONT);
     }
 
-    fn draw_sprite(&mut self) {
-        self.cpu.draw_sprite(&mut self.display);
+    fn draw_sprite(&mut self, x: Reg, y: Reg, height: u8) {
+        self.cpu.draw_sprite(&mut self.display, x, y, height);
         self.should_redraw = true;
+        self.waiting_vblank = self.cpu.quirks.display_wait;
     }
 
     fn clear_screen(&mut self) {
@@ -162,8 +351,90 @@ impl Computer {
         self.cpu.next_instruction();
     }
 
-    fn unknow_opcode_error(&self, opcode: Opcode) -> ! {
-        panic!("Unknown opcode {:#04x}", opcode.value())
+    // Switching the resolution clears the screen
+    fn set_resolution(&mut self, width: u8, height: u8) {
+        self.display.resize(width as usize, height as usize);
+        self.should_clear_screen = true;
+        self.cpu.next_instruction();
+    }
+
+    fn trace(&mut self, pc: usize) {
+        let tracer = match self.tracer.as_mut() {
+            Some(tracer) => tracer,
+            None => return,
+        };
+
+        let record = TraceRecord {
+            cycle: self.cycles,
+            pc: pc as u16,
+            opcode: self.cpu.opcode.value(),
+            regs: self.cpu.regs,
+            i_reg: self.cpu.i_reg,
+            sp: self.cpu.sp as u8,
+            writes: self
+                .cpu
+                .bus
+                .accesses()
+                .iter()
+                .filter(|access| access.kind == AccessKind::Write)
+                .map(|access| (access.addr, access.new_value))
+                .collect(),
+        };
+
+        if let Err(error) = tracer.record(&record) {
+            self.messages.push(format!("Unable to write trace, tracing stopped: {error}"));
+            self.tracer = None;
+        }
+    }
+
+    fn check_self_modification(&mut self, pc: usize) {
+        let smc = match self.smc.as_mut() {
+            Some(smc) => smc,
+            None => return,
+        };
+
+        let modifications = smc.check(pc as u16, &self.cpu.opcode, self.cpu.bus.accesses(), &self.cpu.bus.memory);
+        if modifications.is_empty() {
+            return;
+        }
+
+        for modification in modifications.iter() {
+            self.messages.push(format!("Self-modifying code: {modification}"));
+        }
+        if smc.break_on_write {
+            self.debugger.pause();
+        }
+    }
+
+    fn handle_watch_hits(&mut self) {
+        let hits = self.cpu.bus.take_watch_hits();
+        if hits.is_empty() {
+            return;
+        }
+
+        for hit in hits.iter() {
+            self.messages.push(format!("Watchpoint hit: {hit}"));
+        }
+        self.debugger.pause();
+    }
+
+    fn check_breakpoints(&mut self) {
+        if self.debugger.breakpoints.is_empty() {
+            return;
+        }
+
+        let state = State {
+            cpu: &self.cpu,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            hits: 0,
+        };
+        let messages = self.debugger.check_breakpoints(&state);
+        self.messages.extend(messages);
+    }
+
+    fn unknow_opcode_error(&self, opcode: Opcode) -> Result<(), Fault> {
+        Err(Fault::UnknownOpcode(opcode.value()))
     }
 
 
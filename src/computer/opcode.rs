@@ -4,7 +4,7 @@ use std::fmt;
 pub struct Opcode(u16);
 
 impl Opcode {
-    pub fn new(value: u16) -> Opcode {
+    pub const fn new(value: u16) -> Opcode {
         Opcode(value)
     }
 
@@ -33,7 +33,7 @@ impl Opcode {
         (self.0 & 0x00FF) as u8
     }
 
-    pub fn value(&self) -> u16 {
+    pub const fn value(&self) -> u16 {
         self.0
     }
 }
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use crate::computer::disasm::disassemble;
+use crate::computer::instruction::{decode, Instruction};
+use crate::computer::opcode::Opcode;
+
+// Number of rows in each table of the text report
+const REPORT_ROWS: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubroutineStats {
+    pub calls: u64,
+    // Instructions executed inside the subroutine, including nested calls
+    pub cycles: u64,
+}
+
+// Subroutine call which hasn't returned yet, one for each return address on the CPU stack
+struct Frame {
+    addr: u16,
+    start_cycle: u64,
+}
+
+pub struct Profiler {
+    // Executions per instruction address
+    pub addr_counts: HashMap<u16, u64>,
+    // Executions per opcode pattern, e.g. `8xy4`
+    pub class_counts: HashMap<&'static str, u64>,
+    pub subroutines: HashMap<u16, SubroutineStats>,
+    // Draw instructions executed in each finished frame
+    pub draws_per_frame: Vec<u32>,
+    pub collisions: u64,
+    pub cycles: u64,
+    frames: Vec<Frame>,
+    frame_draws: u32,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            addr_counts: HashMap::new(),
+            class_counts: HashMap::new(),
+            subroutines: HashMap::new(),
+            draws_per_frame: Vec::new(),
+            collisions: 0,
+            cycles: 0,
+            frames: Vec::new(),
+            frame_draws: 0,
+        }
+    }
+
+    // Records instruction executed at `pc`, `vf` and `call_stack` are the CPU state after the execution
+    pub fn record(&mut self, pc: u16, opcode: &Opcode, vf: u8, call_stack: &[u16]) {
+        self.cycles += 1;
+        *self.addr_counts.entry(pc).or_insert(0) += 1;
+        *self.class_counts.entry(opcode_class(opcode)).or_insert(0) += 1;
+
+        // frames follow the CPU stack, so calls which faulted or a program resetting
+        // the stack pointer don't leave frames behind
+        while self.frames.len() > call_stack.len() {
+            if let Some(frame) = self.frames.pop() {
+                let stats = self.subroutines.entry(frame.addr).or_default();
+                stats.cycles += self.cycles - frame.start_cycle;
+            }
+        }
+
+        match decode(opcode) {
+            Instruction::Call(addr) if call_stack.len() > self.frames.len() => {
+                self.subroutines.entry(addr).or_default().calls += 1;
+                self.frames.push(Frame { addr, start_cycle: self.cycles });
+            },
+            Instruction::Drw(..) => {
+                self.frame_draws += 1;
+                if vf == 1 {
+                    self.collisions += 1;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.draws_per_frame.push(self.frame_draws);
+        self.frame_draws = 0;
+    }
+
+    pub fn report(&self, memory: &[u8]) -> String {
+        let mut report = format!("=== Profile: {} instructions, {} frames ===\n", self.cycles, self.draws_per_frame.len());
+
+        report.push_str("\nHotspots:\n");
+        for (addr, count) in sorted_by_count(&self.addr_counts).into_iter().take(REPORT_ROWS) {
+            let addr = *addr as usize;
+            let opcode = match memory.get(addr..addr + 2) {
+                Some(bytes) => disassemble(&Opcode::from(bytes[0], bytes[1])),
+                None => String::new(),
+            };
+            report.push_str(&format!("  {:03x}  {:>10}  {:>6.2}%  {}\n", addr, count, self.percent(*count), opcode));
+        }
+
+        report.push_str("\nOpcode classes:\n");
+        for (class, count) in sorted_by_count(&self.class_counts) {
+            report.push_str(&format!("  {}  {:>10}  {:>6.2}%\n", class, count, self.percent(*count)));
+        }
+
+        report.push_str("\nSubroutines (inclusive instructions):\n");
+        for (addr, stats) in self.subroutine_stats().into_iter().take(REPORT_ROWS) {
+            let average = stats.cycles as f64 / stats.calls.max(1) as f64;
+            report.push_str(&format!(
+                "  {:03x}  calls {:>8}  instructions {:>10} ({:>6.2}%)  avg {:.1}\n",
+                addr, stats.calls, stats.cycles, self.percent(stats.cycles), average
+            ));
+        }
+
+        let total_draws: u64 = self.draws_per_frame.iter().map(|draws| *draws as u64).sum();
+        let max_draws = self.draws_per_frame.iter().max().copied().unwrap_or(0);
+        report.push_str("\nDrawing:\n");
+        report.push_str(&format!("  draws per frame: avg {:.2}, max {}\n", self.average_draws(), max_draws));
+        report.push_str(&format!("  total draws: {}, sprite collisions: {}\n", total_draws, self.collisions));
+
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        let addrs: Vec<String> = sorted_by_count(&self.addr_counts)
+            .iter()
+            .map(|(addr, count)| format!("{{\"addr\": {}, \"count\": {}}}", addr, count))
+            .collect();
+        let classes: Vec<String> = sorted_by_count(&self.class_counts)
+            .iter()
+            .map(|(class, count)| format!("{{\"class\": \"{}\", \"count\": {}}}", class, count))
+            .collect();
+        let subroutines: Vec<String> = self
+            .subroutine_stats()
+            .iter()
+            .map(|(addr, stats)| {
+                format!("{{\"addr\": {}, \"calls\": {}, \"cycles\": {}}}", addr, stats.calls, stats.cycles)
+            })
+            .collect();
+        let draws: Vec<String> = self.draws_per_frame.iter().map(|draws| draws.to_string()).collect();
+
+        format!(
+            "{{\n  \"cycles\": {},\n  \"hotspots\": [{}],\n  \"opcode_classes\": [{}],\n  \"subroutines\": [{}],\n  \"draws_per_frame\": [{}],\n  \"collisions\": {}\n}}\n",
+            self.cycles,
+            addrs.join(", "),
+            classes.join(", "),
+            subroutines.join(", "),
+            draws.join(", "),
+            self.collisions
+        )
+    }
+
+    // Subroutines with the most instructions first; calls which haven't returned yet,
+    // like a main loop entered with CALL, count the instructions executed so far
+    fn subroutine_stats(&self) -> Vec<(u16, SubroutineStats)> {
+        let mut subroutines = self.subroutines.clone();
+        for frame in self.frames.iter() {
+            subroutines.entry(frame.addr).or_default().cycles += self.cycles - frame.start_cycle;
+        }
+
+        let mut sorted: Vec<(u16, SubroutineStats)> = subroutines.into_iter().collect();
+        sorted.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(&b.0)));
+        sorted
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.cycles.max(1) as f64
+    }
+
+    fn average_draws(&self) -> f64 {
+        let total: u64 = self.draws_per_frame.iter().map(|draws| *draws as u64).sum();
+        total as f64 / self.draws_per_frame.len().max(1) as f64
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+// Most frequent first, ties are ordered by key so reports are stable
+fn sorted_by_count<K: Ord>(counts: &HashMap<K, u64>) -> Vec<(&K, &u64)> {
+    let mut sorted: Vec<(&K, &u64)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    sorted
+}
+
+// Opcode pattern as written in the CHIP-8 reference, e.g. `8xy4` or `Fx33`
+pub fn opcode_class(opcode: &Opcode) -> &'static str {
+    use Instruction::*;
+
+    match decode(opcode) {
+        Cls => "00E0",
+        Ret => "00EE",
+        Low => "00FE",
+        High => "00FF",
+        Sys(_) => "0nnn",
+        Jp(_) => "1nnn",
+        Call(_) => "2nnn",
+        SeByte(..) => "3xkk",
+        SneByte(..) => "4xkk",
+        SeReg(..) => "5xy0",
+        LdByte(..) => "6xkk",
+        AddByte(..) => "7xkk",
+        LdReg(..) => "8xy0",
+        Or(..) => "8xy1",
+        And(..) => "8xy2",
+        Xor(..) => "8xy3",
+        AddReg(..) => "8xy4",
+        Sub(..) => "8xy5",
+        Shr(..) => "8xy6",
+        Subn(..) => "8xy7",
+        Shl(..) => "8xyE",
+        SneReg(..) => "9xy0",
+        LdI(_) => "Annn",
+        JpV0(_) => "Bnnn",
+        Rnd(..) => "Cxkk",
+        Drw(..) => "Dxyn",
+        Skp(_) => "Ex9E",
+        Sknp(_) => "ExA1",
+        LdRegDt(_) => "Fx07",
+        LdRegK(_) => "Fx0A",
+        LdDtReg(_) => "Fx15",
+        LdStReg(_) => "Fx18",
+        AddIReg(_) => "Fx1E",
+        LdFReg(_) => "Fx29",
+        LdBReg(_) => "Fx33",
+        LdMemRegs(_) => "Fx55",
+        LdRegsMem(_) => "Fx65",
+        Unknown(_) => "????",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Computer;
+
+    // Runs `rom` with the profiler attached for `steps` instructions
+    fn profile(rom: Vec<u8>, steps: usize) -> Profiler {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer.profiler = Some(Profiler::new());
+        for _ in 0..steps {
+            computer.step();
+        }
+        computer.profiler.unwrap()
+    }
+
+    #[test]
+    fn counts_instructions_and_classes() {
+        let profiler = profile(crate::chip8! {
+            ld v0, 3;
+            loop_start: add v0, 0xff;
+            se v0, 0;
+            jp loop_start
+        }, 9);
+
+        assert_eq!(profiler.cycles, 9);
+        assert_eq!(profiler.addr_counts[&0x202], 3);
+        assert_eq!(profiler.addr_counts[&0x206], 2);
+        assert_eq!(profiler.class_counts["7xkk"], 3);
+        assert_eq!(profiler.class_counts["6xkk"], 1);
+    }
+
+    #[test]
+    fn measures_subroutines_including_nested_calls() {
+        let profiler = profile(crate::chip8! {
+            call outer;
+            call inner;
+            end: jp end;
+            outer: call inner;
+            ret;
+            inner: ld v1, 1;
+            ret
+        }, 9);
+
+        // outer: call inner + inner (ld, ret) + ret, inner: ld and ret in both calls
+        assert_eq!(profiler.subroutines[&0x206].calls, 1);
+        assert_eq!(profiler.subroutines[&0x206].cycles, 4);
+        assert_eq!(profiler.subroutines[&0x20A].calls, 2);
+        assert_eq!(profiler.subroutines[&0x20A].cycles, 4);
+    }
+
+    #[test]
+    fn reports_subroutines_which_never_return() {
+        let profiler = profile(crate::chip8! {
+            call main;
+            main: add v0, 1;
+            jp main
+        }, 5);
+
+        // the main loop is still on the stack, its instructions count up to the end
+        assert_eq!(profiler.subroutine_stats(), [(0x202, SubroutineStats { calls: 1, cycles: 4 })]);
+        assert!(profiler.report(&[0; 4096]).contains("  202  calls        1  instructions          4 ( 80.00%)"));
+        assert!(profiler.to_json().contains("\"subroutines\": [{\"addr\": 514, \"calls\": 1, \"cycles\": 4}]"));
+    }
+
+    #[test]
+    fn follows_the_cpu_stack() {
+        let mut profiler = Profiler::new();
+        // call at 0x200, then the stack is emptied without a return
+        profiler.record(0x200, &Opcode::new(0x2300), 0, &[0x202]);
+        profiler.record(0x300, &Opcode::new(0x6001), 0, &[]);
+        assert_eq!(profiler.subroutines[&0x300].cycles, 1);
+
+        // a call which didn't push a return address isn't counted
+        profiler.record(0x302, &Opcode::new(0x2400), 0, &[]);
+        assert!(!profiler.subroutines.contains_key(&0x400));
+    }
+
+    #[test]
+    fn classes_follow_the_decoder() {
+        assert_eq!(opcode_class(&Opcode::new(0x00FF)), "00FF");
+        assert_eq!(opcode_class(&Opcode::new(0x0123)), "0nnn");
+        assert_eq!(opcode_class(&Opcode::new(0x812E)), "8xyE");
+        assert_eq!(opcode_class(&Opcode::new(0xF265)), "Fx65");
+        // opcodes the decoder doesn't know share one class
+        assert_eq!(opcode_class(&Opcode::new(0x8128)), "????");
+        assert_eq!(opcode_class(&Opcode::new(0xE1FF)), "????");
+    }
+
+    #[test]
+    fn counts_draws_per_frame_and_collisions() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, &Opcode::new(0xD015), 0, &[]);
+        profiler.record(0x202, &Opcode::new(0xD015), 1, &[]);
+        profiler.end_frame();
+        profiler.end_frame();
+
+        assert_eq!(profiler.draws_per_frame, [2, 0]);
+        assert_eq!(profiler.collisions, 1);
+        let report = profiler.report(&[0; 4096]);
+        assert!(report.contains("draws per frame: avg 1.00, max 2"));
+        assert!(report.contains("total draws: 2, sprite collisions: 1"));
+        assert!(profiler.to_json().contains("\"draws_per_frame\": [2, 0]"));
+    }
+
+    #[test]
+    fn report_lists_hotspots_first() {
+        let profiler = profile(crate::chip8! {
+            start: add v0, 1;
+            jp start
+        }, 5);
+        let report = profiler.report(&[0; 4096]);
+        let hotspots: Vec<&str> = report.lines().skip_while(|line| *line != "Hotspots:").skip(1).take(2).collect();
+        assert!(hotspots[0].starts_with("  200           3"));
+        assert!(hotspots[1].starts_with("  202           2"));
+        assert!(profiler.to_json().contains("{\"addr\": 512, \"count\": 3}"));
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+// Behaviour differences between CHIP-8 interpreters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    // 8xy1/8xy2/8xy3 reset VF to 0
+    pub vf_reset: bool,
+    // Fx55/Fx65 leave I pointing past the last register
+    pub memory_increment: bool,
+    // Dxyn waits for the next 60Hz frame
+    pub display_wait: bool,
+    // Sprites are clipped at screen edges instead of wrapping around
+    pub clipping: bool,
+    // 8xy6/8xyE shift VX in place instead of shifting VY into VX
+    pub shifting: bool,
+    // Bnnn jumps to xnn + VX instead of nnn + V0
+    pub jumping: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    // Original COSMAC VIP interpreter
+    Chip8,
+    // SUPER-CHIP 1.1 as it behaves on modern interpreters
+    Schip,
+    // XO-CHIP quirks, extended instructions are not supported
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::Schip, Platform::XoChip];
+
+    pub fn parse(text: &str) -> Result<Platform, String> {
+        match text {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::Schip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!("Unknown platform: {text}, expected chip8, schip or xochip")),
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                display_wait: true,
+                clipping: true,
+                shifting: false,
+                jumping: false,
+            },
+            Platform::Schip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+                jumping: false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "chip8"),
+            Platform::Schip => write!(f, "schip"),
+            Platform::XoChip => write!(f, "xochip"),
+        }
+    }
+}
+
+// Behaviour CRAB8 had before the platform profiles, used when no platform is chosen
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            vf_reset: false,
+            memory_increment: false,
+            display_wait: false,
+            clipping: false,
+            shifting: true,
+            jumping: false,
+        }
+    }
+}
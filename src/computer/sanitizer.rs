@@ -0,0 +1,323 @@
+use std::fmt;
+
+use crate::computer::PROGRAM_START_ADDR;
+use crate::computer::bus::{Access, AccessKind, MEMORY_SIZE};
+use crate::computer::disasm::disassemble;
+use crate::computer::instruction::{decode, Instruction, Reg};
+use crate::computer::opcode::Opcode;
+use crate::computer::quirks::Quirks;
+use crate::utils::FONT;
+
+// Return addresses kept in a report
+const BACKTRACE_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Register(u8),
+    IndexRegister,
+    // Memory read as data or fetched as an instruction
+    Memory(u16, AccessKind),
+}
+
+// First read of a location which was never written
+#[derive(Debug, Clone)]
+pub struct UninitializedRead {
+    pub location: Location,
+    pub pc: u16,
+    pub opcode: u16,
+    // Return addresses, innermost first
+    pub backtrace: Vec<u16>,
+}
+
+impl fmt::Display for UninitializedRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match self.location {
+            Location::Register(index) => format!("read of V{:X}", index),
+            Location::IndexRegister => String::from("read of I"),
+            Location::Memory(addr, AccessKind::Execute) => format!("execution of memory {:#05x}", addr),
+            Location::Memory(addr, _) => format!("read of memory {:#05x}", addr),
+        };
+        let backtrace: Vec<String> = self.backtrace.iter().map(|addr| format!("{:#05x}", addr)).collect();
+
+        write!(
+            f,
+            "uninitialized {} at PC {:#05x} ({:04x} {}), backtrace: [{}]",
+            location,
+            self.pc,
+            self.opcode,
+            disassemble(&Opcode::new(self.opcode)),
+            backtrace.join(" <- ")
+        )
+    }
+}
+
+// Tracks which registers and memory bytes were written and reports reads of the others
+pub struct Sanitizer {
+    regs_written: [bool; 16],
+    i_written: bool,
+    memory_written: Vec<bool>,
+    // Locations already reported, each one is reported only once
+    regs_reported: [bool; 16],
+    i_reported: bool,
+    memory_reported: Vec<bool>,
+    pub reports: Vec<UninitializedRead>,
+}
+
+impl Sanitizer {
+    // Font and ROM are loaded before the program starts, so they count as written
+    pub fn new(rom_size: usize) -> Sanitizer {
+        let mut memory_written = vec![false; MEMORY_SIZE];
+        memory_written[0..FONT.len()].fill(true);
+        let rom_end = (PROGRAM_START_ADDR + rom_size).min(MEMORY_SIZE);
+        memory_written[PROGRAM_START_ADDR..rom_end].fill(true);
+
+        Sanitizer {
+            regs_written: [false; 16],
+            i_written: false,
+            memory_written,
+            regs_reported: [false; 16],
+            i_reported: false,
+            memory_reported: vec![false; MEMORY_SIZE],
+            reports: Vec::new(),
+        }
+    }
+
+    // Checks the executed instruction and returns reports of new uninitialized reads
+    pub fn check(
+        &mut self,
+        pc: u16,
+        opcode: &Opcode,
+        quirks: &Quirks,
+        accesses: &[Access],
+        call_stack: &[u16],
+    ) -> Vec<UninitializedRead> {
+        let mut found = Vec::new();
+        let (regs_read, i_read) = register_reads(opcode, quirks);
+        let (regs_written, i_written) = register_writes(opcode, quirks);
+
+        for index in 0..16 {
+            if regs_read & (1 << index) != 0 && !self.regs_written[index] && !self.regs_reported[index] {
+                self.regs_reported[index] = true;
+                found.push(Location::Register(index as u8));
+            }
+        }
+        if i_read && !self.i_written && !self.i_reported {
+            self.i_reported = true;
+            found.push(Location::IndexRegister);
+        }
+
+        for access in accesses {
+            let addr = access.addr as usize;
+            match access.kind {
+                AccessKind::Write => self.memory_written[addr] = true,
+                kind => {
+                    if !self.memory_written[addr] && !self.memory_reported[addr] {
+                        self.memory_reported[addr] = true;
+                        found.push(Location::Memory(access.addr, kind));
+                    }
+                },
+            }
+        }
+
+        for index in 0..16 {
+            if regs_written & (1 << index) != 0 {
+                self.regs_written[index] = true;
+            }
+        }
+        self.i_written |= i_written;
+
+        let backtrace: Vec<u16> = call_stack.iter().rev().take(BACKTRACE_DEPTH).copied().collect();
+        let found: Vec<UninitializedRead> = found
+            .into_iter()
+            .map(|location| UninitializedRead { location, pc, opcode: opcode.value(), backtrace: backtrace.clone() })
+            .collect();
+
+        self.reports.extend(found.iter().cloned());
+        found
+    }
+}
+
+fn reg_mask(index: Reg) -> u16 {
+    1 << index
+}
+
+// Registers V0..=Vx
+fn reg_range_mask(x: Reg) -> u16 {
+    ((1u32 << (x + 1)) - 1) as u16
+}
+
+// Registers read by the instruction under the quirks as (V0-VF bit mask, reads I)
+fn register_reads(opcode: &Opcode, quirks: &Quirks) -> (u16, bool) {
+    use Instruction::*;
+
+    match decode(opcode) {
+        SeByte(x, _) | SneByte(x, _) | AddByte(x, _) => (reg_mask(x), false),
+        SeReg(x, y) | SneReg(x, y) => (reg_mask(x) | reg_mask(y), false),
+        LdReg(_, y) => (reg_mask(y), false),
+        Or(x, y) | And(x, y) | Xor(x, y) | AddReg(x, y) | Sub(x, y) | Subn(x, y) => (reg_mask(x) | reg_mask(y), false),
+        Shr(x, y) | Shl(x, y) => (reg_mask(if quirks.shifting { x } else { y }), false),
+        JpV0(addr) => (reg_mask(if quirks.jumping { (addr >> 8) as Reg } else { 0 }), false),
+        Drw(x, y, _) => (reg_mask(x) | reg_mask(y), true),
+        Skp(x) | Sknp(x) | LdDtReg(x) | LdStReg(x) | LdFReg(x) => (reg_mask(x), false),
+        AddIReg(x) | LdBReg(x) => (reg_mask(x), true),
+        LdMemRegs(x) => (reg_range_mask(x), true),
+        LdRegsMem(_) => (0, true),
+        _ => (0, false),
+    }
+}
+
+// Registers written by the instruction under the quirks as (V0-VF bit mask, writes I)
+fn register_writes(opcode: &Opcode, quirks: &Quirks) -> (u16, bool) {
+    use Instruction::*;
+
+    let vf = reg_mask(0xF);
+    match decode(opcode) {
+        LdByte(x, _) | AddByte(x, _) | Rnd(x, _) | LdReg(x, _) => (reg_mask(x), false),
+        Or(x, _) | And(x, _) | Xor(x, _) => (reg_mask(x) | if quirks.vf_reset { vf } else { 0 }, false),
+        AddReg(x, _) | Sub(x, _) | Shr(x, _) | Subn(x, _) | Shl(x, _) => (reg_mask(x) | vf, false),
+        LdI(_) => (0, true),
+        Drw(..) => (vf, false),
+        LdRegDt(x) | LdRegK(x) => (reg_mask(x), false),
+        AddIReg(_) | LdFReg(_) => (0, true),
+        LdMemRegs(_) => (0, quirks.memory_increment),
+        LdRegsMem(x) => (reg_range_mask(x), quirks.memory_increment),
+        _ => (0, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::quirks::Platform;
+    use crate::computer::{Computer, Status};
+
+    fn reads(value: u16, quirks: &Quirks) -> (u16, bool) {
+        register_reads(&Opcode::new(value), quirks)
+    }
+
+    fn writes(value: u16, quirks: &Quirks) -> (u16, bool) {
+        register_writes(&Opcode::new(value), quirks)
+    }
+
+    // Runs `rom` under the sanitizer until it halts
+    fn sanitize(rom: Vec<u8>) -> Vec<UninitializedRead> {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.sanitizer = Some(Sanitizer::new(rom.len()));
+        computer.load_rom(rom).unwrap();
+        computer.cpu.quirks = Platform::Chip8.quirks();
+        for _ in 0..64 {
+            if computer.step() != Status::Running {
+                break;
+            }
+        }
+        computer.sanitizer.unwrap().reports
+    }
+
+    #[test]
+    fn reports_registers_read_before_written() {
+        let reports = sanitize(crate::chip8! {
+            ld v0, 1;
+            add v0, v1;
+            add v0, v1;
+            end: jp end
+        });
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].location, Location::Register(1));
+        assert_eq!((reports[0].pc, reports[0].opcode), (0x202, 0x8014));
+        assert_eq!(
+            reports[0].to_string(),
+            "uninitialized read of V1 at PC 0x202 (8014 ADD V0, V1), backtrace: []"
+        );
+    }
+
+    #[test]
+    fn reports_index_register_and_memory() {
+        // I starts at the font, which counts as written
+        let reports = sanitize(crate::chip8! {
+            ld v0, [i];
+            ld i, 0x300;
+            ld [i], v0;
+            ld i, 0x300;
+            ld v1, [i];
+            end: jp end
+        });
+
+        let locations: Vec<Location> = reports.iter().map(|report| report.location).collect();
+        assert_eq!(locations, [Location::IndexRegister, Location::Memory(0x301, AccessKind::Read)]);
+        assert_eq!(reports[1].to_string(), "uninitialized read of memory 0x301 at PC 0x208 (f165 LD V1, [I]), backtrace: []");
+    }
+
+    #[test]
+    fn font_and_rom_count_as_written() {
+        let reports = sanitize(crate::chip8! {
+            ld v0, 0;
+            ld f, v0;
+            drw v0, v0, 5;
+            ld i, data;
+            ld v1, [i];
+            end: jp end;
+            data: db 1, 2
+        });
+        assert!(reports.is_empty(), "{reports:?}");
+    }
+
+    #[test]
+    fn reports_execution_of_uninitialized_memory() {
+        // the ROM ends in the middle of an instruction, 6000 is executed
+        let reports = sanitize(crate::chip8! {
+            ld v1, 1;
+            db 0x60
+        });
+        assert_eq!(reports[0].location, Location::Memory(0x203, AccessKind::Execute));
+        assert!(reports[0].to_string().starts_with("uninitialized execution of memory 0x203 at PC 0x202"));
+    }
+
+    #[test]
+    fn backtrace_lists_return_addresses_innermost_first() {
+        let reports = sanitize(crate::chip8! {
+            call outer;
+            end: jp end;
+            outer: call inner;
+            ret;
+            inner: se v5, 0;
+            ret
+        });
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].location, Location::Register(5));
+        assert_eq!(reports[0].backtrace, [0x206, 0x202]);
+        assert!(reports[0].to_string().ends_with("backtrace: [0x206 <- 0x202]"));
+    }
+
+    #[test]
+    fn reports_each_location_once() {
+        let reports = sanitize(crate::chip8! {
+            start: add v0, v3;
+            se v0, 0;
+            jp start;
+            end: jp end
+        });
+        assert_eq!(reports.len(), 2);
+    }
+
+    #[test]
+    fn register_tables_follow_quirks() {
+        let (chip8, schip) = (Platform::Chip8.quirks(), Platform::Schip.quirks());
+
+        // 8xy6 and 8xyE shift VY unless VX is shifted in place
+        assert_eq!(reads(0x8126, &chip8), (1 << 2, false));
+        assert_eq!(reads(0x812E, &schip), (1 << 1, false));
+        // Bxnn adds VX instead of V0
+        assert_eq!(reads(0xB312, &chip8), (1 << 0, false));
+        assert_eq!(reads(0xB312, &schip), (1 << 3, false));
+        // 8xy1/2/3 reset VF
+        assert_eq!(writes(0x8121, &chip8), (1 << 1 | 1 << 0xF, false));
+        assert_eq!(writes(0x8122, &schip), (1 << 1, false));
+        // Fx55 and Fx65 advance I
+        assert_eq!(writes(0xF255, &chip8), (0, true));
+        assert_eq!(writes(0xF265, &chip8), (0b111, true));
+        assert_eq!(writes(0xF265, &schip), (0b111, false));
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+
+use crate::computer::bus::{Access, AccessKind, MEMORY_SIZE};
+use crate::computer::disasm::disassemble;
+use crate::computer::opcode::Opcode;
+
+// Write into an instruction that was already executed
+#[derive(Debug, Clone)]
+pub struct Modification {
+    // Address of the modified instruction
+    pub addr: u16,
+    // Instruction which made the write
+    pub writer_pc: u16,
+    pub writer_opcode: u16,
+    pub old_opcode: u16,
+    pub new_opcode: u16,
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "instruction at {:#05x} modified by PC {:#05x} ({}): {:04x} {} -> {:04x} {}",
+            self.addr,
+            self.writer_pc,
+            disassemble(&Opcode::new(self.writer_opcode)),
+            self.old_opcode,
+            disassemble(&Opcode::new(self.old_opcode)),
+            self.new_opcode,
+            disassemble(&Opcode::new(self.new_opcode))
+        )
+    }
+}
+
+// Detects self-modifying code: writes to addresses fetched as opcodes
+pub struct SmcDetector {
+    // Addresses where fetched instructions start
+    fetched: Vec<bool>,
+    // Pause the debugger on every modification
+    pub break_on_write: bool,
+    pub modifications: Vec<Modification>,
+}
+
+impl SmcDetector {
+    pub fn new(break_on_write: bool) -> SmcDetector {
+        SmcDetector {
+            fetched: vec![false; MEMORY_SIZE],
+            break_on_write,
+            modifications: Vec::new(),
+        }
+    }
+
+    // Checks accesses of the executed instruction, returns modifications it made
+    pub fn check(&mut self, pc: u16, opcode: &Opcode, accesses: &[Access], memory: &[u8]) -> Vec<Modification> {
+        self.fetched[pc as usize] = true;
+
+        // modified instruction addresses with their bytes before the instruction,
+        // writes are walked backwards so the first write to a byte wins
+        let mut modified: Vec<(u16, [u8; 2])> = Vec::new();
+        for access in accesses.iter().rev().filter(|access| access.kind == AccessKind::Write) {
+            let addr = access.addr as usize;
+            let instruction_addr = if self.fetched[addr] {
+                addr
+            } else if addr > 0 && self.fetched[addr - 1] {
+                addr - 1
+            } else {
+                continue;
+            };
+
+            let index = match modified.iter().position(|(modified_addr, _)| *modified_addr as usize == instruction_addr) {
+                Some(index) => index,
+                None => {
+                    let bytes = [memory[instruction_addr], memory[(instruction_addr + 1) % MEMORY_SIZE]];
+                    modified.push((instruction_addr as u16, bytes));
+                    modified.len() - 1
+                },
+            };
+            modified[index].1[addr - instruction_addr] = access.old_value;
+        }
+
+        let found: Vec<Modification> = modified
+            .into_iter()
+            .map(|(addr, old_bytes)| Modification {
+                addr,
+                writer_pc: pc,
+                writer_opcode: opcode.value(),
+                old_opcode: Opcode::from(old_bytes[0], old_bytes[1]).value(),
+                new_opcode: Opcode::from(memory[addr as usize], memory[(addr as usize + 1) % MEMORY_SIZE]).value(),
+            })
+            .filter(|modification| modification.old_opcode != modification.new_opcode)
+            .collect();
+
+        self.modifications.extend(found.iter().cloned());
+        found
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!("Self-modifying code: {} modified instructions\n", self.modifications.len());
+        for modification in self.modifications.iter() {
+            report.push_str(&format!("  {}\n", modification));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Computer;
+
+    // Runs `rom` for `steps` instructions with self-modification detection
+    fn run(rom: Vec<u8>, break_on_write: bool, steps: usize) -> Computer {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer.smc = Some(SmcDetector::new(break_on_write));
+        for _ in 0..steps {
+            computer.step();
+        }
+        computer
+    }
+
+    #[test]
+    fn reports_writes_into_executed_instructions() {
+        let computer = run(
+            crate::chip8! {
+                target: ld v1, 5;
+                ld i, target;
+                ld v0, 0x71;
+                ld [i], v0;
+                end: jp end
+            },
+            false,
+            5,
+        );
+
+        let modifications = &computer.smc.as_ref().unwrap().modifications;
+        assert_eq!(modifications.len(), 1);
+        let modification = &modifications[0];
+        assert_eq!((modification.addr, modification.writer_pc, modification.writer_opcode), (0x200, 0x206, 0xf055));
+        assert_eq!((modification.old_opcode, modification.new_opcode), (0x6105, 0x7105));
+        assert_eq!(
+            modification.to_string(),
+            "instruction at 0x200 modified by PC 0x206 (LD [I], V0): 6105 LD V1, 0x05 -> 7105 ADD V1, 0x05"
+        );
+        assert_eq!(computer.messages, [format!("Self-modifying code: {modification}")]);
+        assert!(!computer.debugger.paused);
+    }
+
+    #[test]
+    fn merges_writes_into_one_instruction() {
+        // both bytes of the executed instruction are stored by a single Fx55
+        let computer = run(
+            crate::chip8! {
+                target: ld v1, 5;
+                ld i, target;
+                ld v0, 0x71;
+                ld v1, 0x06;
+                ld [i], v1;
+                end: jp end
+            },
+            false,
+            6,
+        );
+
+        let modifications = &computer.smc.as_ref().unwrap().modifications;
+        assert_eq!(modifications.len(), 1);
+        assert_eq!((modifications[0].addr, modifications[0].old_opcode, modifications[0].new_opcode), (0x200, 0x6105, 0x7106));
+    }
+
+    #[test]
+    fn detects_writes_into_second_byte() {
+        let computer = run(
+            crate::chip8! {
+                target: ld v1, 5;
+                ld i, 0x201;
+                ld v0, 0x07;
+                ld [i], v0;
+                end: jp end
+            },
+            false,
+            5,
+        );
+
+        let modifications = &computer.smc.as_ref().unwrap().modifications;
+        assert_eq!(modifications.len(), 1);
+        assert_eq!((modifications[0].addr, modifications[0].new_opcode), (0x200, 0x6107));
+    }
+
+    #[test]
+    fn ignores_unexecuted_and_unchanged_instructions() {
+        let computer = run(
+            crate::chip8! {
+                target: ld v1, 5;
+                ld i, target;
+                ld v0, 0x61;
+                ld [i], v0;
+                ld i, 0x300;
+                ld [i], v0;
+                end: jp end
+            },
+            false,
+            7,
+        );
+
+        assert!(computer.smc.as_ref().unwrap().modifications.is_empty());
+        assert!(computer.messages.is_empty());
+    }
+
+    #[test]
+    fn pauses_on_write_when_asked() {
+        let computer = run(
+            crate::chip8! {
+                target: ld v1, 5;
+                ld i, target;
+                ld v0, 0x71;
+                ld [i], v0;
+                end: jp end
+            },
+            true,
+            5,
+        );
+
+        assert!(computer.debugger.paused);
+        // execution stops right after the write
+        assert_eq!(computer.cpu.pc, 0x208);
+        assert_eq!(computer.smc.as_ref().unwrap().report().lines().next(), Some("Self-modifying code: 1 modified instructions"));
+    }
+}
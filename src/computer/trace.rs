@@ -0,0 +1,406 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+use crate::computer::bus::parse_addr;
+use crate::computer::disasm::disassemble;
+use crate::computer::opcode::Opcode;
+
+// Binary traces start with this header, followed by records
+pub const BINARY_MAGIC: &[u8; 8] = b"CRAB8TR\x02";
+// cycle (u64) + pc (u16) + opcode (u16) + regs (16 x u8) + i (u16) + sp (u8)
+// + number of writes (u8), followed by writes as addr (u16) + value (u8), little-endian
+pub const BINARY_RECORD_SIZE: usize = 32;
+const BINARY_WRITE_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+impl TraceFormat {
+    pub fn parse(value: &str) -> Result<TraceFormat, String> {
+        match value {
+            "text" => Ok(TraceFormat::Text),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!("Unknown trace format: {value}")),
+        }
+    }
+}
+
+// Executed instruction with the machine state after its execution
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    // Number of instructions executed before this one
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub regs: [u8; 16],
+    pub i_reg: u16,
+    pub sp: u8,
+    // Memory written by the instruction as (address, value)
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl TraceRecord {
+    // `cycle=12 pc=204 op=6108 v=0c08...00 i=22a sp=0 w=300:01,301:02 ; LD V1, 0x08`
+    pub fn to_text(&self) -> String {
+        let regs: String = self.regs.iter().map(|value| format!("{:02x}", value)).collect();
+        let mut line = format!(
+            "cycle={} pc={:03x} op={:04x} v={} i={:03x} sp={:x}",
+            self.cycle, self.pc, self.opcode, regs, self.i_reg, self.sp
+        );
+
+        if !self.writes.is_empty() {
+            let writes: Vec<String> = self
+                .writes
+                .iter()
+                .map(|(addr, value)| format!("{:03x}:{:02x}", addr, value))
+                .collect();
+            line.push_str(&format!(" w={}", writes.join(",")));
+        }
+
+        format!("{} ; {}", line, disassemble(&Opcode::new(self.opcode)))
+    }
+
+    pub fn parse_text(line: &str) -> Result<TraceRecord, String> {
+        let fields = line.split(" ; ").next().unwrap_or_default();
+        let mut record = TraceRecord {
+            cycle: 0,
+            pc: 0,
+            opcode: 0,
+            regs: [0; 16],
+            i_reg: 0,
+            sp: 0,
+            writes: Vec::new(),
+        };
+        let invalid = || format!("Invalid trace line: {line}");
+        let hex = |value: &str| u16::from_str_radix(value, 16).map_err(|_| invalid());
+
+        for field in fields.split_whitespace() {
+            let (key, value) = field.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "cycle" => record.cycle = value.parse().map_err(|_| invalid())?,
+                "pc" => record.pc = hex(value)?,
+                "op" => record.opcode = hex(value)?,
+                "i" => record.i_reg = hex(value)?,
+                "sp" => record.sp = hex(value)? as u8,
+                "v" => {
+                    // byte offsets below are only char boundaries in ASCII text
+                    if value.len() != 32 || !value.is_ascii() {
+                        return Err(invalid());
+                    }
+                    for (index, reg) in record.regs.iter_mut().enumerate() {
+                        *reg = hex(&value[index * 2..index * 2 + 2])? as u8;
+                    }
+                },
+                "w" => {
+                    for write in value.split(',') {
+                        let (addr, value) = write.split_once(':').ok_or_else(invalid)?;
+                        record.writes.push((hex(addr)?, hex(value)? as u8));
+                    }
+                },
+                // unknown fields are skipped, so traces may carry extra data
+                _ => {},
+            }
+        }
+
+        Ok(record)
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BINARY_RECORD_SIZE + self.writes.len() * BINARY_WRITE_SIZE);
+        bytes.extend_from_slice(&self.cycle.to_le_bytes());
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&self.opcode.to_le_bytes());
+        bytes.extend_from_slice(&self.regs);
+        bytes.extend_from_slice(&self.i_reg.to_le_bytes());
+        bytes.push(self.sp);
+        // instructions write at most 16 bytes (Fx55)
+        bytes.push(self.writes.len() as u8);
+
+        for (addr, value) in self.writes.iter() {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+            bytes.push(*value);
+        }
+
+        bytes
+    }
+
+    // Parses record at the start of `bytes`, returns it with its size
+    pub fn parse_binary(bytes: &[u8]) -> Result<(TraceRecord, usize), String> {
+        if bytes.len() < BINARY_RECORD_SIZE {
+            return Err(String::from("Truncated binary trace record"));
+        }
+
+        let u16_at = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+        let writes_count = bytes[31] as usize;
+        let size = BINARY_RECORD_SIZE + writes_count * BINARY_WRITE_SIZE;
+        if bytes.len() < size {
+            return Err(String::from("Truncated binary trace record"));
+        }
+
+        let mut regs = [0; 16];
+        regs.copy_from_slice(&bytes[12..28]);
+
+        let writes = (0..writes_count)
+            .map(|index| {
+                let offset = BINARY_RECORD_SIZE + index * BINARY_WRITE_SIZE;
+                (u16_at(offset), bytes[offset + 2])
+            })
+            .collect();
+
+        let mut cycle = [0; 8];
+        cycle.copy_from_slice(&bytes[0..8]);
+
+        let record = TraceRecord {
+            cycle: u64::from_le_bytes(cycle),
+            pc: u16_at(8),
+            opcode: u16_at(10),
+            regs,
+            i_reg: u16_at(28),
+            sp: bytes[30],
+            writes,
+        };
+
+        Ok((record, size))
+    }
+}
+
+// Reads a text or binary trace, the format is detected by the binary header
+pub fn read_trace(path: &str) -> Result<Vec<TraceRecord>, String> {
+    let data = fs::read(path).map_err(|e| format!("Unable to read trace {path}: {e}"))?;
+
+    if let Some(mut rest) = data.strip_prefix(BINARY_MAGIC.as_slice()) {
+        let mut records = Vec::new();
+        while !rest.is_empty() {
+            let (record, size) = TraceRecord::parse_binary(rest)?;
+            records.push(record);
+            rest = &rest[size..];
+        }
+        return Ok(records);
+    }
+
+    String::from_utf8_lossy(&data)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(TraceRecord::parse_text)
+        .collect()
+}
+
+// Limits which instructions get into the trace
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub pc_range: Option<RangeInclusive<u16>>,
+    pub cycle_range: Option<RangeInclusive<u64>>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, record: &TraceRecord) -> bool {
+        let pc_matches = self.pc_range.as_ref().is_none_or(|range| range.contains(&record.pc));
+        let cycle_matches = self.cycle_range.as_ref().is_none_or(|range| range.contains(&record.cycle));
+        pc_matches && cycle_matches
+    }
+
+    // Parses `START-END` address range, e.g. `0x200-0x2ff`
+    pub fn parse_pc_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or(format!("Invalid address range: {value}"))?;
+        let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+        // a reversed range would filter out everything
+        if start > end {
+            return Err(format!("Invalid address range: {value}"));
+        }
+        Ok(start..=end)
+    }
+
+    // Parses `START-END` cycle window, either end may be omitted, e.g. `1000-`
+    pub fn parse_cycle_range(value: &str) -> Result<RangeInclusive<u64>, String> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or(format!("Invalid cycle range: {value}"))?;
+        let parse = |value: &str, default: u64| match value.trim() {
+            "" => Ok(default),
+            value => value.parse::<u64>().map_err(|_| format!("Invalid cycle: {value}")),
+        };
+        let (start, end) = (parse(start, 0)?, parse(end, u64::MAX)?);
+        if start > end {
+            return Err(format!("Invalid cycle range: {value}"));
+        }
+        Ok(start..=end)
+    }
+}
+
+pub struct Tracer {
+    writer: BufWriter<File>,
+    format: TraceFormat,
+    pub filter: TraceFilter,
+}
+
+impl Tracer {
+    pub fn create(path: &str, format: TraceFormat, filter: TraceFilter) -> io::Result<Tracer> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == TraceFormat::Binary {
+            writer.write_all(BINARY_MAGIC)?;
+        }
+
+        Ok(Tracer { writer, format, filter })
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        if !self.filter.matches(record) {
+            return Ok(());
+        }
+
+        match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", record.to_text()),
+            TraceFormat::Binary => self.writer.write_all(&record.to_binary()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> TraceRecord {
+        let mut regs = [0; 16];
+        regs[0] = 0x0C;
+        regs[1] = 0x08;
+        regs[0xF] = 1;
+        TraceRecord {
+            cycle: 12,
+            pc: 0x204,
+            opcode: 0xF155,
+            regs,
+            i_reg: 0x300,
+            sp: 2,
+            writes: vec![(0x300, 0x0C), (0x301, 0x08)],
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = record().to_text();
+        assert_eq!(
+            text,
+            "cycle=12 pc=204 op=f155 v=0c080000000000000000000000000001 i=300 sp=2 w=300:0c,301:08 ; LD [I], V1"
+        );
+        assert_eq!(TraceRecord::parse_text(&text).unwrap(), record());
+
+        let no_writes = TraceRecord { writes: Vec::new(), ..record() };
+        assert_eq!(TraceRecord::parse_text(&no_writes.to_text()).unwrap(), no_writes);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut bytes = record().to_binary();
+        assert_eq!(bytes.len(), BINARY_RECORD_SIZE + 2 * BINARY_WRITE_SIZE);
+        bytes.extend_from_slice(&[0xFF; 4]);
+
+        let (parsed, size) = TraceRecord::parse_binary(&bytes).unwrap();
+        assert_eq!(parsed, record());
+        assert_eq!(size, BINARY_RECORD_SIZE + 2 * BINARY_WRITE_SIZE);
+    }
+
+    // Writes the records through a tracer, returns the file contents and the records read back
+    fn write_trace(name: &str, format: TraceFormat, filter: TraceFilter) -> (Vec<u8>, Vec<TraceRecord>) {
+        let path = std::env::temp_dir().join(format!("crab8-{}-{name}", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+
+        let mut tracer = Tracer::create(&path, format, filter).unwrap();
+        for record in records() {
+            tracer.record(&record).unwrap();
+        }
+        tracer.flush().unwrap();
+
+        let data = fs::read(&path).unwrap();
+        let read_back = read_trace(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        (data, read_back)
+    }
+
+    fn records() -> Vec<TraceRecord> {
+        (0..4).map(|cycle| TraceRecord { cycle, pc: 0x200 + cycle as u16 * 2, ..record() }).collect()
+    }
+
+    #[test]
+    fn tracer_writes_text_lines() {
+        let (data, read_back) = write_trace("text.log", TraceFormat::Text, TraceFilter::default());
+        let text = String::from_utf8(data).unwrap();
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.starts_with("cycle=0 pc=200 op=f155 "));
+        assert_eq!(read_back, records());
+    }
+
+    #[test]
+    fn tracer_writes_binary_with_header() {
+        let (data, read_back) = write_trace("binary.bin", TraceFormat::Binary, TraceFilter::default());
+        assert!(data.starts_with(BINARY_MAGIC));
+        assert_eq!(data.len(), BINARY_MAGIC.len() + 4 * (BINARY_RECORD_SIZE + 2 * BINARY_WRITE_SIZE));
+        assert_eq!(read_back, records());
+    }
+
+    #[test]
+    fn tracer_skips_filtered_records() {
+        let filter = TraceFilter {
+            pc_range: Some(TraceFilter::parse_pc_range("0x202-0x206").unwrap()),
+            cycle_range: Some(TraceFilter::parse_cycle_range("-2").unwrap()),
+        };
+        let (_, read_back) = write_trace("filtered.log", TraceFormat::Text, filter);
+        assert_eq!(read_back, records()[1..=2]);
+    }
+
+    #[test]
+    fn computer_traces_state_and_writes() {
+        let path = std::env::temp_dir().join(format!("crab8-{}-computer.log", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+
+        let mut computer = crate::computer::Computer::new();
+        computer.reset();
+        computer.load_rom(crate::chip8! { ld v0, 5; ld i, 0x300; ld [i], v0 }).unwrap();
+        computer.tracer = Some(Tracer::create(&path, TraceFormat::Text, TraceFilter::default()).unwrap());
+        for _ in 0..3 {
+            computer.step();
+        }
+        computer.tracer.as_mut().unwrap().flush().unwrap();
+
+        let trace = read_trace(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(trace.len(), 3);
+        assert_eq!((trace[1].cycle, trace[1].pc, trace[1].i_reg), (1, 0x202, 0x300));
+        assert_eq!(trace[2].writes, [(0x300, 5)]);
+    }
+
+    #[test]
+    fn parses_filter_ranges() {
+        assert_eq!(TraceFilter::parse_pc_range("0x200-0x2ff").unwrap(), 0x200..=0x2FF);
+        assert_eq!(TraceFilter::parse_cycle_range("1000-").unwrap(), 1000..=u64::MAX);
+        assert_eq!(TraceFilter::parse_cycle_range("-5").unwrap(), 0..=5);
+        assert!(TraceFilter::parse_pc_range("0x200").is_err());
+        assert!(TraceFilter::parse_cycle_range("a-b").is_err());
+        assert_eq!(TraceFilter::parse_pc_range("0x2ff-0x200").err(), Some(String::from("Invalid address range: 0x2ff-0x200")));
+        assert!(TraceFilter::parse_cycle_range("5000-1000").is_err());
+        assert_eq!(TraceFormat::parse("binary").unwrap(), TraceFormat::Binary);
+        assert!(TraceFormat::parse("json").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        assert!(TraceRecord::parse_text("cycle=x pc=200").is_err());
+        assert!(TraceRecord::parse_text("pc=200 v=00").is_err());
+        assert!(TraceRecord::parse_text(&format!("pc=200 v=\u{20ac}{}", "0".repeat(29))).is_err());
+        assert!(TraceRecord::parse_text("pc=200 w=300").is_err());
+        assert!(TraceRecord::parse_text("garbage").is_err());
+
+        let bytes = record().to_binary();
+        assert!(TraceRecord::parse_binary(&bytes[..BINARY_RECORD_SIZE - 1]).is_err());
+        assert!(TraceRecord::parse_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
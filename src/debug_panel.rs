@@ -0,0 +1,278 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::computer::Computer;
+use crate::computer::bus::MEMORY_SIZE;
+use crate::computer::disasm::disassemble;
+use crate::computer::opcode::Opcode;
+
+pub const PANEL_WIDTH: u32 = 480;
+
+// Size of a font pixel on screen
+const TEXT_SCALE: i32 = 2;
+const CHAR_WIDTH: i32 = 5 * TEXT_SCALE;
+const LINE_HEIGHT: i32 = 6 * TEXT_SCALE;
+const MARGIN: i32 = 8;
+
+const MEMORY_ROWS: i32 = 8;
+const MEMORY_ROW_BYTES: i32 = 8;
+// Instructions shown before and after PC
+const DISASM_CONTEXT: i32 = 3;
+
+// Hex keypad layout
+const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+const TEXT_COLOR: Color = Color::RGB(200, 200, 200);
+const HIGHLIGHT_COLOR: Color = Color::RGB(255, 200, 0);
+const BACKGROUND_COLOR: Color = Color::RGB(24, 24, 32);
+
+// 4x5 glyphs in the same format as the CHIP-8 font, only the upper nibble is used
+const PANEL_FONT: [(char, [u8; 5]); 51] = [
+    ('0', [0xF0, 0x90, 0x90, 0x90, 0xF0]),
+    ('1', [0x20, 0x60, 0x20, 0x20, 0x70]),
+    ('2', [0xF0, 0x10, 0xF0, 0x80, 0xF0]),
+    ('3', [0xF0, 0x10, 0xF0, 0x10, 0xF0]),
+    ('4', [0x90, 0x90, 0xF0, 0x10, 0x10]),
+    ('5', [0xF0, 0x80, 0xF0, 0x10, 0xF0]),
+    ('6', [0xF0, 0x80, 0xF0, 0x90, 0xF0]),
+    ('7', [0xF0, 0x10, 0x20, 0x40, 0x40]),
+    ('8', [0xF0, 0x90, 0xF0, 0x90, 0xF0]),
+    ('9', [0xF0, 0x90, 0xF0, 0x10, 0xF0]),
+    ('A', [0xF0, 0x90, 0xF0, 0x90, 0x90]),
+    ('B', [0xE0, 0x90, 0xE0, 0x90, 0xE0]),
+    ('C', [0xF0, 0x80, 0x80, 0x80, 0xF0]),
+    ('D', [0xE0, 0x90, 0x90, 0x90, 0xE0]),
+    ('E', [0xF0, 0x80, 0xF0, 0x80, 0xF0]),
+    ('F', [0xF0, 0x80, 0xF0, 0x80, 0x80]),
+    ('G', [0xF0, 0x80, 0xB0, 0x90, 0xF0]),
+    ('H', [0x90, 0x90, 0xF0, 0x90, 0x90]),
+    ('I', [0x70, 0x20, 0x20, 0x20, 0x70]),
+    ('J', [0x10, 0x10, 0x10, 0x90, 0x60]),
+    ('K', [0x90, 0xA0, 0xC0, 0xA0, 0x90]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('N', [0x90, 0xD0, 0xB0, 0x90, 0x90]),
+    ('O', [0x60, 0x90, 0x90, 0x90, 0x60]),
+    ('P', [0xE0, 0x90, 0xE0, 0x80, 0x80]),
+    ('Q', [0x60, 0x90, 0x90, 0xB0, 0x70]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0xF0, 0x80, 0xF0, 0x10, 0xF0]),
+    ('T', [0xF0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0x90, 0x90, 0x90, 0x90, 0xF0]),
+    ('V', [0x90, 0x90, 0x90, 0x60, 0x60]),
+    ('W', [0x90, 0x90, 0xF0, 0xF0, 0x90]),
+    ('X', [0x90, 0x90, 0x60, 0x90, 0x90]),
+    ('Y', [0x90, 0x90, 0x60, 0x40, 0x40]),
+    ('Z', [0xF0, 0x10, 0x60, 0x80, 0xF0]),
+    ('x', [0x00, 0x90, 0x60, 0x60, 0x90]),
+    (':', [0x00, 0x40, 0x00, 0x40, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x40, 0x80]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    ('[', [0x60, 0x40, 0x40, 0x40, 0x60]),
+    (']', [0x60, 0x20, 0x20, 0x20, 0x60]),
+    ('>', [0x80, 0x40, 0x20, 0x40, 0x80]),
+    ('<', [0x20, 0x40, 0x80, 0x40, 0x20]),
+    ('-', [0x00, 0x00, 0xF0, 0x00, 0x00]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('=', [0x00, 0xF0, 0x00, 0xF0, 0x00]),
+    ('#', [0xA0, 0xF0, 0xA0, 0xF0, 0xA0]),
+    ('?', [0xE0, 0x10, 0x60, 0x00, 0x40]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+];
+
+// Side panel with live machine state, toggled with F1
+pub struct DebugPanel {
+    pub visible: bool,
+    // Memory view offset in rows from the row containing I
+    memory_scroll: i32,
+}
+
+impl DebugPanel {
+    pub fn new() -> DebugPanel {
+        DebugPanel { visible: false, memory_scroll: 0 }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn scroll_memory(&mut self, rows: i32) {
+        self.memory_scroll += rows;
+    }
+
+    // Makes memory view follow I again
+    pub fn reset_memory_scroll(&mut self) {
+        self.memory_scroll = 0;
+    }
+
+    // Draws the panel with its left edge at `x`, canvas scale is restored afterwards
+    pub fn draw(&self, canvas: &mut Canvas<Window>, computer: &Computer, x: i32) -> Result<(), String> {
+        let (scale_x, scale_y) = canvas.scale();
+        canvas.set_scale(1.0, 1.0)?;
+
+        let (_, height) = canvas.output_size()?;
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.fill_rect(Rect::new(x, 0, PANEL_WIDTH, height))?;
+
+        let mut text = PanelText { canvas, x: x + MARGIN, line: 0 };
+        text.draw_registers(computer)?;
+        text.draw_stack(computer)?;
+        text.draw_keypad_and_disassembly(computer)?;
+        text.draw_memory(computer, self.memory_scroll)?;
+
+        canvas.set_scale(scale_x, scale_y)
+    }
+}
+
+impl Default for DebugPanel {
+    fn default() -> Self {
+        DebugPanel::new()
+    }
+}
+
+struct PanelText<'a> {
+    canvas: &'a mut Canvas<Window>,
+    // Left edge of the text
+    x: i32,
+    // Current line
+    line: i32,
+}
+
+impl PanelText<'_> {
+    fn draw_registers(&mut self, computer: &Computer) -> Result<(), String> {
+        let cpu = &computer.cpu;
+
+        for (row_index, values) in cpu.regs.chunks(4).enumerate() {
+            let line: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("V{:X} {:02X}", row_index * 4 + index, value))
+                .collect();
+            self.print(0, &line.join("  "), TEXT_COLOR)?;
+            self.line += 1;
+        }
+
+        self.print(0, &format!("I {:03X}  PC {:03X}  SP {:X}", cpu.i_reg, cpu.pc, cpu.sp), TEXT_COLOR)?;
+        self.line += 1;
+
+        let status = if computer.debugger.paused { "PAUSED" } else { "" };
+        let timers = format!("DT {:02X}  ST {:02X}  {}", computer.delay_timer, computer.sound_timer, status);
+        self.print(0, &timers, TEXT_COLOR)?;
+        self.line += 2;
+
+        Ok(())
+    }
+
+    fn draw_stack(&mut self, computer: &Computer) -> Result<(), String> {
+        // most recent return address first
+        let frames: Vec<String> = computer
+            .cpu
+            .call_stack()
+            .iter()
+            .rev()
+            .map(|addr| format!("{:03X}", addr))
+            .collect();
+
+        let first_line = self.line;
+        self.print(0, "STACK", TEXT_COLOR)?;
+        for (row, chunk) in frames.chunks(8).enumerate() {
+            self.line = first_line + row as i32;
+            self.print(6, &chunk.join(" "), TEXT_COLOR)?;
+        }
+
+        self.line = first_line + 3;
+        Ok(())
+    }
+
+    fn draw_keypad_and_disassembly(&mut self, computer: &Computer) -> Result<(), String> {
+        let first_line = self.line;
+
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, key) in keys.iter().enumerate() {
+                let color = if computer.keyboard.keys[*key] { HIGHLIGHT_COLOR } else { TEXT_COLOR };
+                self.line = first_line + 1 + row as i32;
+                self.print(column as i32 * 2, &format!("{:X}", key), color)?;
+            }
+        }
+
+        let cpu = &computer.cpu;
+        for offset in -DISASM_CONTEXT..=DISASM_CONTEXT {
+            self.line = first_line + offset + DISASM_CONTEXT;
+            let addr = cpu.pc as i32 + offset * 2;
+            if addr < 0 || addr + 1 >= MEMORY_SIZE as i32 {
+                continue;
+            }
+
+            let addr = addr as usize;
+            let opcode = Opcode::from(cpu.bus.memory[addr], cpu.bus.memory[addr + 1]);
+            let marker = if offset == 0 { ">" } else { " " };
+            let color = if offset == 0 { HIGHLIGHT_COLOR } else { TEXT_COLOR };
+            let line = format!("{}{:03X} {:04X} {}", marker, addr, opcode.value(), disassemble(&opcode));
+            self.print(10, &line, color)?;
+        }
+
+        self.line = first_line + DISASM_CONTEXT * 2 + 2;
+        Ok(())
+    }
+
+    fn draw_memory(&mut self, computer: &Computer, scroll: i32) -> Result<(), String> {
+        let i_reg = computer.cpu.i_reg as i32;
+        let total_rows = MEMORY_SIZE as i32 / MEMORY_ROW_BYTES;
+        let first_row = (i_reg / MEMORY_ROW_BYTES - MEMORY_ROWS / 2 + scroll)
+            .clamp(0, total_rows - MEMORY_ROWS);
+
+        for row in first_row..first_row + MEMORY_ROWS {
+            let row_addr = row * MEMORY_ROW_BYTES;
+            self.print(0, &format!("{:03X}", row_addr), TEXT_COLOR)?;
+
+            for column in 0..MEMORY_ROW_BYTES {
+                let addr = row_addr + column;
+                let value = computer.cpu.bus.memory[addr as usize];
+                let color = if addr == i_reg { HIGHLIGHT_COLOR } else { TEXT_COLOR };
+                self.print(5 + column * 3, &format!("{:02X}", value), color)?;
+            }
+            self.line += 1;
+        }
+
+        Ok(())
+    }
+
+    // Prints text at the current line starting from the given column
+    fn print(&mut self, column: i32, text: &str, color: Color) -> Result<(), String> {
+        self.canvas.set_draw_color(color);
+        let y = MARGIN + self.line * LINE_HEIGHT;
+
+        for (index, char) in text.chars().enumerate() {
+            let char = if char == 'x' { char } else { char.to_ascii_uppercase() };
+            let glyph = match PANEL_FONT.iter().find(|(glyph_char, _)| *glyph_char == char) {
+                Some((_, glyph)) => glyph,
+                None => continue,
+            };
+            let char_x = self.x + (column + index as i32) * CHAR_WIDTH;
+
+            for (row, bits) in glyph.iter().enumerate() {
+                for bit in 0..4 {
+                    if bits & (0x80 >> bit) != 0 {
+                        let rect = Rect::new(
+                            char_x + bit * TEXT_SCALE,
+                            y + row as i32 * TEXT_SCALE,
+                            TEXT_SCALE as u32,
+                            TEXT_SCALE as u32,
+                        );
+                        self.canvas.fill_rect(rect)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
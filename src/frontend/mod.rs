@@ -0,0 +1,252 @@
+// Frontends connect the computer to a screen, a speaker and a keypad; `Runner` drives the
+// computer frame by frame against any combination of them
+
+pub mod sdl;
+pub mod testing;
+
+use crate::computer::{Computer, Status};
+use crate::scheduler::Scheduler;
+
+// Frontend actions which don't go to the emulated keypad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoCommand {
+    TogglePanel,
+    ScrollMemory(i32),
+    ResetMemoryScroll,
+    NextPalette,
+    ToggleGrid,
+    NextFilter,
+    ToggleFullscreen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    // Keypad key 0x0..=0xF pressed or released
+    Key(usize, bool),
+    // Debugger: pause/resume and step one instruction while paused
+    TogglePause,
+    Step,
+    Video(VideoCommand),
+    Quit,
+}
+
+pub trait VideoSink {
+    // Shows the screen at the end of every frame
+    fn present(&mut self, computer: &Computer, status: &Status) -> Result<(), String>;
+
+    fn command(&mut self, _command: VideoCommand) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Shows a message of the debugger or analysis tools, stdout may belong to the frontend
+    fn message(&mut self, text: &str) {
+        eprintln!("{text}");
+    }
+}
+
+pub trait AudioSink {
+    // Called every frame, the tone sounds while the sound timer is running
+    fn set_tone(&mut self, on: bool);
+}
+
+pub trait InputSource {
+    // Events which happened before `frame`
+    fn poll(&mut self, frame: u64) -> Result<Vec<InputEvent>, String>;
+
+    // Whether events may still come at `frame` or later, live input always may
+    fn pending(&self, _frame: u64) -> bool {
+        true
+    }
+}
+
+pub struct Runner<V: VideoSink, A: AudioSink, I: InputSource> {
+    pub video: V,
+    pub audio: A,
+    pub input: I,
+    // Run frames at 60Hz instead of as fast as possible
+    pub realtime: bool,
+    pub stop_on_halt: bool,
+    // Stop when paused, or waiting for a key the input will never deliver
+    pub stop_when_idle: bool,
+    // Stop after this many frames
+    pub frames: Option<u64>,
+    frame: u64,
+}
+
+impl<V: VideoSink, A: AudioSink, I: InputSource> Runner<V, A, I> {
+    pub fn new(video: V, audio: A, input: I) -> Runner<V, A, I> {
+        Runner {
+            video,
+            audio,
+            input,
+            realtime: false,
+            stop_on_halt: false,
+            stop_when_idle: false,
+            frames: None,
+            frame: 0,
+        }
+    }
+
+    // Number of frames run so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Runs until the input quits, a fault, the frame limit, a halt when `stop_on_halt` is set
+    // or no progress when `stop_when_idle` is set, returns status of the last frame
+    pub fn run(&mut self, computer: &mut Computer) -> Result<Status, String> {
+        let mut scheduler = Scheduler::new();
+        let mut status = Status::Running;
+        let mut last_status = Status::Running;
+
+        'running: while self.frames.is_none_or(|frames| self.frame < frames) {
+            for event in self.input.poll(self.frame)? {
+                match event {
+                    InputEvent::Key(key, pressed) => computer.set_key(key, pressed),
+                    InputEvent::TogglePause => computer.debugger.toggle_pause(),
+                    InputEvent::Step => computer.debugger.step(),
+                    InputEvent::Video(command) => self.video.command(command)?,
+                    InputEvent::Quit => break 'running,
+                }
+            }
+
+            status = computer.run_frame();
+            self.frame += 1;
+            for message in std::mem::take(&mut computer.messages) {
+                self.video.message(&message);
+            }
+            if let Some(message) = status_message(&last_status, &status) {
+                self.video.message(&message);
+            }
+            last_status = status;
+            self.video.present(computer, &status)?;
+            self.audio.set_tone(computer.sound_timer > 0);
+
+            match status {
+                Status::Fault(_) => break,
+                Status::Halted(_) if self.stop_on_halt => break,
+                Status::Paused if self.stop_when_idle => break,
+                Status::WaitingKey if self.stop_when_idle && !self.input.pending(self.frame) => break,
+                _ => {}
+            }
+
+            if self.realtime {
+                scheduler.wait();
+            }
+        }
+
+        self.audio.set_tone(false);
+        Ok(status)
+    }
+}
+
+// Status changes reported to the user, every frontend shows them as messages
+fn status_message(previous: &Status, status: &Status) -> Option<String> {
+    match status {
+        Status::WaitingKey if *previous != Status::WaitingKey => Some(String::from("waiting key...")),
+        Status::Halted(reason) if !matches!(previous, Status::Halted(_)) => Some(format!("Halted: {reason}")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::testing::{QueuedInput, RecordedAudio, RecordedVideo};
+
+    fn computer(rom: Vec<u8>) -> Computer {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer
+    }
+
+    #[test]
+    fn runs_frames_and_stops_on_quit() {
+        let mut computer = computer(crate::chip8! {
+            loop_start: add v0, 1;
+            jp loop_start
+        });
+        let input = QueuedInput::new(vec![(5, InputEvent::Quit)]);
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), input);
+
+        assert_eq!(runner.run(&mut computer).unwrap(), Status::Running);
+        assert_eq!(runner.frame(), 5);
+        assert_eq!(runner.video.presented, 5);
+    }
+
+    #[test]
+    fn keys_reach_the_computer_and_tone_follows_sound_timer() {
+        // waits for a key, then beeps for 2 frames and halts
+        let mut computer = computer(crate::chip8! {
+            ld v0, k;
+            ld v1, 2;
+            ld st, v1;
+            end: jp end
+        });
+        let input = QueuedInput::new(vec![(1, InputEvent::Key(5, true)), (2, InputEvent::Key(5, false))]);
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), input);
+        runner.frames = Some(10);
+
+        assert!(matches!(runner.run(&mut computer).unwrap(), Status::Halted(_)));
+        assert_eq!(computer.cpu.regs[0], 5);
+        assert_eq!(runner.audio.beeps, 1);
+        assert!(!runner.audio.tone);
+        assert_eq!(runner.frame(), 10);
+    }
+
+    #[test]
+    fn messages_go_to_the_video_sink() {
+        let mut computer = computer(crate::chip8! {
+            ld v0, 7;
+            end: jp end
+        });
+        let tracepoint = crate::computer::debugger::Breakpoint::parse_tracepoint("0x202 => v0={v0}").unwrap();
+        computer.debugger.add_breakpoint(tracepoint);
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), QueuedInput::new(Vec::new()));
+        runner.stop_on_halt = true;
+
+        runner.run(&mut computer).unwrap();
+        assert_eq!(runner.video.messages.first().map(String::as_str), Some("[0x202] v0=7"));
+        assert!(computer.messages.is_empty());
+    }
+
+    #[test]
+    fn stops_on_halt_when_asked() {
+        let mut computer = computer(crate::chip8! {
+            end: jp end
+        });
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), QueuedInput::new(Vec::new()));
+        runner.stop_on_halt = true;
+
+        assert!(matches!(runner.run(&mut computer).unwrap(), Status::Halted(_)));
+        assert_eq!(runner.frame(), 1);
+        assert!(matches!(runner.video.last_status, Some(Status::Halted(_))));
+        assert_eq!(runner.video.messages, ["Halted: jump to itself at 0x200"]);
+    }
+
+    #[test]
+    fn stops_when_idle_once_input_runs_out() {
+        let rom = crate::chip8! {
+            ld v0, k;
+            ld v0, k;
+            end: jp end
+        };
+        let mut first = computer(rom.clone());
+        let input = QueuedInput::new(vec![(3, InputEvent::Key(1, true)), (4, InputEvent::Key(1, false))]);
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), input);
+        runner.stop_when_idle = true;
+
+        // the first key is delivered, the second one never comes
+        assert_eq!(runner.run(&mut first).unwrap(), Status::WaitingKey);
+        assert_eq!(runner.frame(), 5);
+        assert_eq!(first.cpu.regs[0], 1);
+
+        let mut paused = computer(rom);
+        paused.debugger.pause();
+        let mut runner = Runner::new(RecordedVideo::new(), RecordedAudio::new(), QueuedInput::new(Vec::new()));
+        runner.stop_when_idle = true;
+        assert_eq!(runner.run(&mut paused).unwrap(), Status::Paused);
+        assert_eq!(runner.frame(), 1);
+    }
+}
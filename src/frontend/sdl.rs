@@ -0,0 +1,263 @@
+// Window frontend: texture renderer with the debug panel, square wave buzzer and keyboard input
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+use sdl2::{AudioSubsystem, EventPump};
+
+use crate::computer::{Computer, Status};
+use crate::computer::keyboard::Keyboard;
+use crate::debug_panel::{DebugPanel, PANEL_WIDTH};
+use crate::frontend::{AudioSink, InputEvent, InputSource, Runner, VideoCommand, VideoSink};
+use crate::options::Options;
+use crate::palette::{self, Palette};
+use crate::renderer::{fit_display, to_color, Renderer};
+
+const TONE_FREQUENCY: f32 = 440.0;
+const TONE_VOLUME: f32 = 0.15;
+
+pub struct SdlVideo<'a> {
+    canvas: Canvas<Window>,
+    renderer: Renderer<'a>,
+    debug_panel: DebugPanel,
+    palettes: Vec<Palette>,
+    palette_index: usize,
+    integer_scale: bool,
+}
+
+impl<'a> SdlVideo<'a> {
+    pub fn new(
+        canvas: Canvas<Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        options: &Options,
+    ) -> Result<SdlVideo<'a>, String> {
+        let mut renderer = Renderer::new(texture_creator, options.palette.clone())?;
+        renderer.grid = options.grid;
+        renderer.persistence.set_mode(options.persistence);
+        renderer.filter = options.filter;
+
+        Ok(SdlVideo {
+            canvas,
+            renderer,
+            debug_panel: DebugPanel::new(),
+            palettes: palette::cycle_list(options.palette.clone()),
+            palette_index: 0,
+            integer_scale: options.integer_scale,
+        })
+    }
+
+    fn toggle_panel(&mut self) -> Result<(), String> {
+        self.debug_panel.toggle();
+        // the panel gets its own space next to the display, unless there's no room to grow
+        let window = self.canvas.window_mut();
+        if window.fullscreen_state() == FullscreenType::Off {
+            let (width, height) = window.size();
+            let width = if self.debug_panel.visible {
+                width + PANEL_WIDTH
+            } else {
+                width.saturating_sub(PANEL_WIDTH).max(1)
+            };
+            window.set_size(width, height).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window.set_fullscreen(fullscreen)
+    }
+}
+
+impl VideoSink for SdlVideo<'_> {
+    fn present(&mut self, computer: &Computer, _status: &Status) -> Result<(), String> {
+        // the whole screen is uploaded once per frame, independently of what the ROM drew
+        self.renderer.update(&computer.display)?;
+        self.canvas.set_draw_color(to_color(self.renderer.palette.background()));
+        self.canvas.clear();
+        // layout follows the window size, which may change at any time
+        let (output_width, output_height) = self.canvas.output_size()?;
+        let panel_width = if self.debug_panel.visible { PANEL_WIDTH.min(output_width) } else { 0 };
+        let area_width = output_width - panel_width;
+        let target = fit_display(area_width, output_height, self.renderer.display_size(), self.integer_scale);
+        self.renderer.draw(&mut self.canvas, target)?;
+
+        // debug panel shows live state, so it's redrawn every frame
+        if self.debug_panel.visible {
+            self.debug_panel.draw(&mut self.canvas, computer, area_width as i32)?;
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+
+    fn command(&mut self, command: VideoCommand) -> Result<(), String> {
+        match command {
+            VideoCommand::TogglePanel => self.toggle_panel()?,
+            VideoCommand::ScrollMemory(rows) => self.debug_panel.scroll_memory(rows),
+            VideoCommand::ResetMemoryScroll => self.debug_panel.reset_memory_scroll(),
+            VideoCommand::NextPalette => {
+                self.palette_index = (self.palette_index + 1) % self.palettes.len();
+                self.renderer.palette = self.palettes[self.palette_index].clone();
+                self.message(&format!("Palette: {}", self.renderer.palette));
+            },
+            VideoCommand::ToggleGrid => self.renderer.grid = !self.renderer.grid,
+            VideoCommand::NextFilter => {
+                self.renderer.filter = self.renderer.filter.next();
+                self.message(&format!("Filter: {}", self.renderer.filter));
+            },
+            VideoCommand::ToggleFullscreen => self.toggle_fullscreen()?,
+        }
+        Ok(())
+    }
+
+    // The window leaves the console free for messages
+    fn message(&mut self, text: &str) {
+        println!("{text}");
+    }
+}
+
+struct SquareWave {
+    phase_step: f32,
+    phase: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = if self.phase < 0.5 { TONE_VOLUME } else { -TONE_VOLUME };
+            self.phase = (self.phase + self.phase_step) % 1.0;
+        }
+    }
+}
+
+// Buzzer, silent when the machine has no audio device
+pub struct SdlAudio {
+    device: Option<AudioDevice<SquareWave>>,
+    tone: bool,
+}
+
+impl SdlAudio {
+    pub fn new(audio_subsystem: Result<AudioSubsystem, String>) -> SdlAudio {
+        let desired = AudioSpecDesired { freq: Some(44100), channels: Some(1), samples: None };
+        let device = audio_subsystem.and_then(|audio| {
+            audio.open_playback(None, &desired, |spec| SquareWave {
+                phase_step: TONE_FREQUENCY / spec.freq as f32,
+                phase: 0.0,
+            })
+        });
+
+        match device {
+            Ok(device) => SdlAudio { device: Some(device), tone: false },
+            Err(e) => {
+                println!("Sound disabled: {e}");
+                SdlAudio { device: None, tone: false }
+            },
+        }
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn set_tone(&mut self, on: bool) {
+        if on == self.tone {
+            return;
+        }
+        self.tone = on;
+
+        if let Some(device) = &self.device {
+            if on {
+                device.resume();
+            } else {
+                device.pause();
+            }
+        }
+    }
+}
+
+pub struct SdlInput {
+    event_pump: EventPump,
+    // Only used for mapping keycodes to the keypad
+    keyboard: Keyboard,
+}
+
+impl SdlInput {
+    pub fn new(event_pump: EventPump) -> SdlInput {
+        SdlInput { event_pump, keyboard: Keyboard::new() }
+    }
+}
+
+// Window controls, or the keypad key mapped to the keycode
+fn key_event(keyboard: &Keyboard, keycode: Keycode) -> Option<InputEvent> {
+    let command = match keycode {
+        Keycode::Escape => return Some(InputEvent::Quit),
+        // Debugger: F5 - pause/resume, F10 - step one instruction
+        Keycode::F5 => return Some(InputEvent::TogglePause),
+        Keycode::F10 => return Some(InputEvent::Step),
+        // Debug panel: F1 - show/hide, PageUp/PageDown/Home - scroll memory view
+        Keycode::F1 => VideoCommand::TogglePanel,
+        Keycode::PageUp => VideoCommand::ScrollMemory(-1),
+        Keycode::PageDown => VideoCommand::ScrollMemory(1),
+        Keycode::Home => VideoCommand::ResetMemoryScroll,
+        // Display: F2 - next palette, F3 - show/hide pixel grid, F4 - next upscaling filter
+        Keycode::F2 => VideoCommand::NextPalette,
+        Keycode::F3 => VideoCommand::ToggleGrid,
+        Keycode::F4 => VideoCommand::NextFilter,
+        // Window: F11 - fullscreen on/off
+        Keycode::F11 => VideoCommand::ToggleFullscreen,
+        _ => return keyboard.key_index(keycode).map(|key| InputEvent::Key(key, true)),
+    };
+    Some(InputEvent::Video(command))
+}
+
+impl InputSource for SdlInput {
+    fn poll(&mut self, _frame: u64) -> Result<Vec<InputEvent>, String> {
+        let mut events = Vec::new();
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => events.push(InputEvent::Quit),
+                Event::KeyDown { keycode: Some(keycode), .. } => events.extend(key_event(&self.keyboard, keycode)),
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key) = self.keyboard.key_index(keycode) {
+                        events.push(InputEvent::Key(key, false));
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+}
+
+// Runs the window until it's closed, returns status of the last frame
+pub fn run(computer: &mut Computer, options: &Options) -> Result<Status, String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let window_width = computer.display.width() as u32 * options.scale;
+    let window_height = computer.display.height() as u32 * options.scale;
+
+    let window = video_subsystem
+        .window("CRAB-8", window_width, window_height)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let canvas = window.into_canvas().software().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+
+    let video = SdlVideo::new(canvas, &texture_creator, options)?;
+    let audio = SdlAudio::new(sdl_context.audio());
+    let input = SdlInput::new(sdl_context.event_pump()?);
+
+    let mut runner = Runner::new(video, audio, input);
+    runner.realtime = true;
+    runner.stop_on_halt = options.exit_on_halt;
+    runner.run(computer)
+}
@@ -0,0 +1,97 @@
+// Frontend parts for tests: scripted input and sinks recording what they were given
+
+use crate::computer::{Computer, Status};
+use crate::frontend::{AudioSink, InputEvent, InputSource, VideoCommand, VideoSink};
+
+pub struct RecordedVideo {
+    // Number of presented frames
+    pub presented: u64,
+    pub last_screen: Vec<u8>,
+    pub last_status: Option<Status>,
+    pub commands: Vec<VideoCommand>,
+    pub messages: Vec<String>,
+}
+
+impl RecordedVideo {
+    pub fn new() -> RecordedVideo {
+        RecordedVideo {
+            presented: 0,
+            last_screen: Vec::new(),
+            last_status: None,
+            commands: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+impl Default for RecordedVideo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VideoSink for RecordedVideo {
+    fn present(&mut self, computer: &Computer, status: &Status) -> Result<(), String> {
+        self.presented += 1;
+        self.last_screen = computer.display.memory.to_vec();
+        self.last_status = Some(*status);
+        Ok(())
+    }
+
+    fn command(&mut self, command: VideoCommand) -> Result<(), String> {
+        self.commands.push(command);
+        Ok(())
+    }
+
+    fn message(&mut self, text: &str) {
+        self.messages.push(text.to_string());
+    }
+}
+
+pub struct RecordedAudio {
+    pub tone: bool,
+    // Number of times the tone started
+    pub beeps: u64,
+}
+
+impl RecordedAudio {
+    pub fn new() -> RecordedAudio {
+        RecordedAudio { tone: false, beeps: 0 }
+    }
+}
+
+impl Default for RecordedAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSink for RecordedAudio {
+    fn set_tone(&mut self, on: bool) {
+        if on && !self.tone {
+            self.beeps += 1;
+        }
+        self.tone = on;
+    }
+}
+
+// Events delivered at the start of the given frames
+pub struct QueuedInput {
+    events: Vec<(u64, InputEvent)>,
+}
+
+impl QueuedInput {
+    pub fn new(events: Vec<(u64, InputEvent)>) -> QueuedInput {
+        QueuedInput { events }
+    }
+}
+
+impl InputSource for QueuedInput {
+    fn poll(&mut self, frame: u64) -> Result<Vec<InputEvent>, String> {
+        Ok(self.events.iter().filter(|(at, _)| *at == frame).map(|(_, event)| *event).collect())
+    }
+
+    fn pending(&self, frame: u64) -> bool {
+        self.events.iter().any(|(at, _)| *at >= frame)
+    }
+}
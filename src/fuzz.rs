@@ -0,0 +1,147 @@
+// Bodies of the fuzz targets in `fuzz/`, kept here so they can also run as plain tests
+
+use tinyrand::{Rand, Seeded, StdRand};
+
+use crate::computer::bus::MEMORY_SIZE;
+use crate::computer::coverage::Coverage;
+use crate::computer::display::{HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+use crate::computer::profiler::Profiler;
+use crate::computer::sanitizer::Sanitizer;
+use crate::computer::smc::SmcDetector;
+use crate::computer::{Computer, Status, MAX_ROM_SIZE};
+use crate::utils::png::crc32;
+
+pub const MAX_STEPS: usize = 4096;
+// Average number of instructions between two key events
+const KEY_EVENT_INTERVAL: u16 = 64;
+
+// Computer with the input loaded as ROM, key input is derived from the input too,
+// so every crash is reproducible from the input alone. None when the input doesn't fit into memory
+fn load(data: &[u8]) -> Option<(Computer, StdRand)> {
+    let mut computer = Computer::new();
+    computer.reset();
+    if computer.load_rom(data.to_vec()).is_err() {
+        assert!(data.len() > MAX_ROM_SIZE, "ROM of {} bytes rejected", data.len());
+        return None;
+    }
+    computer.cpu.seed(crc32(data) as u64);
+
+    Some((computer, StdRand::seed(crc32(data).rotate_left(16) as u64)))
+}
+
+fn random_key_event(computer: &mut Computer, rand: &mut StdRand) {
+    if rand.next_lim_u16(KEY_EVENT_INTERVAL) == 0 {
+        let key = rand.next_lim_u16(16) as usize;
+        let pressed = rand.next_bool(tinyrand::Probability::new(0.5));
+        computer.set_key(key, pressed);
+    }
+}
+
+pub fn check_invariants(computer: &Computer) {
+    let cpu = &computer.cpu;
+    assert!(cpu.sp <= cpu.stack.len(), "SP out of range: {}", cpu.sp);
+    assert!(cpu.pc < MEMORY_SIZE, "PC out of memory: {:#x}", cpu.pc);
+    let display = &computer.display;
+    let size = (display.width(), display.height());
+    assert!(
+        size == (WIDTH as usize, HEIGHT as usize) || size == (HIRES_WIDTH as usize, HIRES_HEIGHT as usize),
+        "display size isn't a CHIP-8 or SCHIP resolution: {}x{}",
+        size.0,
+        size.1
+    );
+    assert_eq!(display.memory.len(), size.0 * size.1, "display memory doesn't match its size");
+    assert!(display.memory.iter().all(|pixel| *pixel <= 1), "display holds values other than 0 and 1");
+}
+
+// Runs arbitrary bytes as a ROM with random key input, the interpreter must not panic
+pub fn run_interpreter(data: &[u8]) {
+    let Some((mut computer, mut rand)) = load(data) else {
+        return;
+    };
+
+    for _ in 0..MAX_STEPS {
+        random_key_event(&mut computer, &mut rand);
+        if computer.waiting_vblank {
+            computer.tick_timers();
+        }
+
+        match computer.step() {
+            Status::Fault(_) | Status::Halted(_) => break,
+            _ => check_invariants(&computer),
+        }
+    }
+}
+
+// Runs the input on the plain interpreter and again with every analysis tool attached,
+// the tools only observe the execution, so both runs must end in the same state
+pub fn run_instrumented(data: &[u8]) {
+    let (Some((mut plain, mut plain_rand)), Some((mut instrumented, mut instrumented_rand))) = (load(data), load(data))
+    else {
+        return;
+    };
+    let rom_size = data.len();
+    instrumented.profiler = Some(Profiler::new());
+    instrumented.coverage = Some(Coverage::new(rom_size));
+    instrumented.sanitizer = Some(Sanitizer::new(rom_size));
+    instrumented.smc = Some(SmcDetector::new(false));
+    instrumented.halt_detector.detect_loops = true;
+    plain.halt_detector.detect_loops = true;
+
+    for _ in 0..MAX_STEPS {
+        random_key_event(&mut plain, &mut plain_rand);
+        random_key_event(&mut instrumented, &mut instrumented_rand);
+        for computer in [&mut plain, &mut instrumented] {
+            if computer.waiting_vblank {
+                computer.tick_timers();
+            }
+        }
+
+        let plain_status = plain.step();
+        let instrumented_status = instrumented.step();
+        assert_eq!(plain_status, instrumented_status);
+        assert_eq!(plain.cpu.pc, instrumented.cpu.pc);
+        assert_eq!(plain.cpu.regs, instrumented.cpu.regs);
+        assert_eq!(plain.cpu.i_reg, instrumented.cpu.i_reg);
+        assert_eq!(plain.cpu.call_stack(), instrumented.cpu.call_stack());
+
+        if matches!(plain_status, Status::Fault(_) | Status::Halted(_)) {
+            break;
+        }
+    }
+
+    assert!(plain.cpu.bus.memory == instrumented.cpu.bus.memory, "memory differs");
+    assert!(plain.display.memory == instrumented.display.memory, "display differs");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Short deterministic run of the fuzz targets over random inputs
+    #[test]
+    fn fuzz_targets_smoke() {
+        let mut rand = StdRand::seed(8);
+        for size in [0, 1, 2, 64, 1024, MAX_ROM_SIZE + 16] {
+            for _ in 0..8 {
+                let data: Vec<u8> = (0..size).map(|_| rand.next_u16() as u8).collect();
+                run_interpreter(&data);
+                run_instrumented(&data);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "display memory doesn't match its size")]
+    fn invariants_check_display_memory() {
+        let mut computer = Computer::new();
+        computer.display.memory.pop();
+        check_invariants(&computer);
+    }
+
+    #[test]
+    fn invariants_allow_high_resolution() {
+        let mut computer = Computer::new();
+        computer.display.resize(HIRES_WIDTH as usize, HIRES_HEIGHT as usize);
+        check_invariants(&computer);
+    }
+}
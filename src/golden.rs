@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use crate::computer::{Computer, Status};
+use crate::computer::quirks::Platform;
+use crate::utils;
+
+pub const DEFAULT_MANIFEST: &str = "tests/golden/manifest.list";
+// Seed used when the manifest entry doesn't set one
+const DEFAULT_SEED: u64 = 0;
+
+// ROM run described by one manifest line: `NAME ROM FRAMES [SEED] [PLATFORM]`
+pub struct GoldenCase {
+    pub name: String,
+    pub rom_path: PathBuf,
+    pub frames: u64,
+    pub seed: u64,
+    // Quirks of the platform, CRAB8's original behaviour without one
+    pub platform: Option<Platform>,
+}
+
+pub enum GoldenResult {
+    Match,
+    // Screen differs from the golden, holds the visual diff
+    Mismatch(String),
+    Missing,
+    Blessed,
+}
+
+impl GoldenCase {
+    // ROM path is relative to the manifest directory
+    pub fn parse(line: &str, dir: &Path) -> Result<GoldenCase, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 3 || parts.len() > 5 {
+            return Err(format!("Invalid golden entry: {line}, expected `NAME ROM FRAMES [SEED] [PLATFORM]`"));
+        }
+
+        let frames = parts[2].parse().map_err(|_| format!("Invalid frame count in golden entry: {line}"))?;
+        // the optional fields are told apart by their form, seeds are numbers
+        let mut seed = DEFAULT_SEED;
+        let mut platform = None;
+        for (index, part) in parts.iter().enumerate().skip(3) {
+            if index == 3 && part.starts_with(|c: char| c.is_ascii_digit()) {
+                seed = part.parse().map_err(|_| format!("Invalid seed in golden entry: {line}"))?;
+            } else if platform.is_none() {
+                platform = Some(Platform::parse(part)?);
+            } else {
+                return Err(format!("Invalid golden entry: {line}, expected `NAME ROM FRAMES [SEED] [PLATFORM]`"));
+            }
+        }
+
+        Ok(GoldenCase {
+            name: parts[0].to_string(),
+            rom_path: dir.join(parts[1]),
+            frames,
+            seed,
+            platform,
+        })
+    }
+
+    // Screen after running the ROM, in `Display::to_ascii` format
+    pub fn render(&self) -> Result<String, String> {
+        let rom_path = self.rom_path.to_string_lossy();
+        let rom_data = utils::load_rom(&rom_path).map_err(|e| format!("Unable to load ROM {rom_path}: {e}"))?;
+
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom_data).map_err(|e| format!("Unable to load ROM {rom_path}: {e}"))?;
+        computer.cpu.seed(self.seed);
+        if let Some(platform) = self.platform {
+            computer.cpu.quirks = platform.quirks();
+        }
+
+        for _ in 0..self.frames {
+            match computer.run_frame() {
+                Status::Fault(fault) => return Err(format!("{}: emulation fault: {fault}", self.name)),
+                Status::Halted(_) => break,
+                _ => {}
+            }
+        }
+
+        Ok(computer.display.to_ascii())
+    }
+}
+
+pub fn load_manifest(path: &str) -> Result<Vec<GoldenCase>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read golden manifest {path}: {e}"))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| GoldenCase::parse(line, dir))
+        .collect()
+}
+
+// Compares the screen with `<manifest dir>/<NAME>.txt`, or overwrites it when blessing
+pub fn check(case: &GoldenCase, dir: &Path, bless: bool) -> Result<GoldenResult, String> {
+    let screen = case.render()?;
+    let golden_path = dir.join(format!("{}.txt", case.name));
+
+    if bless {
+        std::fs::write(&golden_path, &screen)
+            .map_err(|e| format!("Unable to write golden {}: {e}", golden_path.display()))?;
+        return Ok(GoldenResult::Blessed);
+    }
+
+    let golden = match std::fs::read_to_string(&golden_path) {
+        Ok(golden) => golden,
+        Err(_) => return Ok(GoldenResult::Missing),
+    };
+
+    if golden == screen {
+        Ok(GoldenResult::Match)
+    } else {
+        Ok(GoldenResult::Mismatch(visual_diff(&golden, &screen)))
+    }
+}
+
+// Screen overlay: `#`/`.` where both agree, `+` lit only in actual, `-` lit only in golden
+pub fn visual_diff(golden: &str, actual: &str) -> String {
+    let mut golden_lines = golden.lines();
+    let mut actual_lines = actual.lines();
+    let mut diff = String::new();
+
+    loop {
+        let (golden_line, actual_line) = match (golden_lines.next(), actual_lines.next()) {
+            (None, None) => break,
+            (golden_line, actual_line) => (golden_line.unwrap_or(""), actual_line.unwrap_or("")),
+        };
+
+        let mut golden_pixels = golden_line.chars();
+        let mut actual_pixels = actual_line.chars();
+        loop {
+            let pixel = match (golden_pixels.next(), actual_pixels.next()) {
+                (None, None) => break,
+                (Some('#'), Some('#')) => '#',
+                (Some('#'), _) => '-',
+                (_, Some('#')) => '+',
+                _ => '.',
+            };
+            diff.push(pixel);
+        }
+        diff.push('\n');
+    }
+
+    diff
+}
+
+// `crab8 golden [MANIFEST] [--bless]`, returns true if all screens match
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let mut manifest_path = DEFAULT_MANIFEST;
+    let mut bless = false;
+
+    for arg in args.iter() {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+            _ => manifest_path = arg.as_str(),
+        }
+    }
+
+    let cases = load_manifest(manifest_path)?;
+    let dir = Path::new(manifest_path).parent().unwrap_or(Path::new("."));
+    let mut failures = 0;
+
+    for case in cases.iter() {
+        match check(case, dir, bless)? {
+            GoldenResult::Match => println!("ok       {}", case.name),
+            GoldenResult::Blessed => println!("blessed  {}", case.name),
+            GoldenResult::Missing => {
+                println!("MISSING  {} (run with --bless to create it)", case.name);
+                failures += 1;
+            },
+            GoldenResult::Mismatch(diff) => {
+                println!("MISMATCH {} (+ extra pixel, - missing pixel)", case.name);
+                print!("{diff}");
+                let diff_path = dir.join(format!("{}.diff.txt", case.name));
+                std::fs::write(&diff_path, diff)
+                    .map_err(|e| format!("Unable to write diff {}: {e}", diff_path.display()))?;
+                failures += 1;
+            },
+        }
+    }
+
+    println!("{} golden screens, {} failed", cases.len(), failures);
+    Ok(failures == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_screens_match() {
+        let manifest_path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), DEFAULT_MANIFEST);
+        let dir = Path::new(&manifest_path).parent().unwrap();
+
+        for case in load_manifest(&manifest_path).unwrap() {
+            match check(&case, dir, false).unwrap() {
+                GoldenResult::Match => {},
+                GoldenResult::Mismatch(diff) => panic!("{} differs from golden screen:\n{diff}", case.name),
+                _ => panic!("{} has no golden screen, run `crab8 golden --bless`", case.name),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_optional_seed_and_platform() {
+        let dir = Path::new("tests");
+        let case = GoldenCase::parse("clip roms/clip.ch8 10 schip", dir).unwrap();
+        assert_eq!((case.seed, case.platform), (DEFAULT_SEED, Some(Platform::Schip)));
+        let case = GoldenCase::parse("random roms/random.ch8 10 42 chip8", dir).unwrap();
+        assert_eq!((case.seed, case.platform), (42, Some(Platform::Chip8)));
+        assert_eq!(GoldenCase::parse("font roms/font.ch8 10", dir).unwrap().platform, None);
+
+        assert!(GoldenCase::parse("clip roms/clip.ch8 10 cosmac", dir).is_err());
+        assert!(GoldenCase::parse("clip roms/clip.ch8 10 chip8 42", dir).is_err());
+    }
+
+    #[test]
+    fn visual_diff_marks_changed_pixels() {
+        assert_eq!(visual_diff("#.#.\n", "##..\n"), "#+-.\n");
+    }
+}
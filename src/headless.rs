@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use crate::computer::{Computer, Status};
+use crate::frontend::{AudioSink, InputEvent, InputSource, Runner, VideoSink};
+use crate::options::Options;
+use crate::utils::png;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyAction {
+    Down,
+    Up,
+    // Down for a single frame
+    Press,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: usize,
+    pub action: KeyAction,
+}
+
+impl KeyEvent {
+    // Parses `FRAME KEY ACTION`, e.g. `120 a press`
+    pub fn parse(text: &str) -> Result<KeyEvent, String> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(format!("Invalid key event: {text}, expected `FRAME KEY down|up|press`"));
+        }
+
+        let frame = parts[0].parse().map_err(|_| format!("Invalid frame in key event: {text}"))?;
+        let key = usize::from_str_radix(parts[1].trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|key| *key < 16)
+            .ok_or(format!("Invalid key in key event: {text}, expected 0-f"))?;
+        let action = match parts[2] {
+            "down" => KeyAction::Down,
+            "up" => KeyAction::Up,
+            "press" => KeyAction::Press,
+            _ => return Err(format!("Invalid action in key event: {text}, expected down, up or press")),
+        };
+
+        Ok(KeyEvent { frame, key, action })
+    }
+}
+
+// Key input applied at the start of frames
+pub struct KeyScript {
+    events: Vec<KeyEvent>,
+}
+
+impl KeyScript {
+    // `spec` is a file path or an inline script, events are separated
+    // by commas or new lines, `#` starts a comment
+    pub fn parse(spec: &str) -> Result<KeyScript, String> {
+        let text = if Path::new(spec).is_file() {
+            std::fs::read_to_string(spec).map_err(|e| format!("Unable to read key script {spec}: {e}"))?
+        } else {
+            spec.to_string()
+        };
+
+        let mut events = Vec::new();
+        for entry in text.lines().flat_map(|line| line.split('#').next().unwrap_or("").split(',')) {
+            if entry.trim().is_empty() {
+                continue;
+            }
+            events.push(KeyEvent::parse(entry)?);
+        }
+        events.sort_by_key(|event| event.frame);
+
+        Ok(KeyScript { events })
+    }
+
+    // Key states to set at `frame` as (key, pressed)
+    pub fn keys_at(&self, frame: u64) -> Vec<(usize, bool)> {
+        let mut keys = Vec::new();
+        for event in self.events.iter() {
+            match event.action {
+                KeyAction::Down if event.frame == frame => keys.push((event.key, true)),
+                KeyAction::Up if event.frame == frame => keys.push((event.key, false)),
+                KeyAction::Press if event.frame == frame => keys.push((event.key, true)),
+                KeyAction::Press if event.frame + 1 == frame => keys.push((event.key, false)),
+                _ => {}
+            }
+        }
+        keys
+    }
+}
+
+impl InputSource for &KeyScript {
+    fn poll(&mut self, frame: u64) -> Result<Vec<InputEvent>, String> {
+        Ok(self.keys_at(frame).into_iter().map(|(key, pressed)| InputEvent::Key(key, pressed)).collect())
+    }
+
+    fn pending(&self, frame: u64) -> bool {
+        self.events.iter().any(|event| match event.action {
+            // the release comes a frame after the press
+            KeyAction::Press => event.frame + 1 >= frame,
+            _ => event.frame >= frame,
+        })
+    }
+}
+
+// Headless runs have no screen and no speaker, the final state is reported instead
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _computer: &Computer, _status: &Status) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_tone(&mut self, _on: bool) {}
+}
+
+// Runs the loaded ROM without a window and prints the final state as JSON,
+// runs until halt or fault when no frame count is given
+pub fn run(computer: &mut Computer, options: &Options) -> Result<Status, String> {
+    let script = match &options.keys {
+        Some(spec) => KeyScript::parse(spec)?,
+        None => KeyScript { events: Vec::new() },
+    };
+
+    let (frame, status) = run_frames(computer, &script, options.frames);
+
+    if let Some(path) = &options.png_path {
+        std::fs::write(path, screen_png(computer)).map_err(|e| format!("Unable to write PNG {path}: {e}"))?;
+    }
+
+    print!("{}", to_json(computer, &options.rom_name, frame, &status));
+    Ok(status)
+}
+
+// Runs frames with scripted keys until halt or fault, or `frames` when given, without a frame
+// count it also stops when paused or waiting for a key the script doesn't press,
+// returns number of frames run and status of the last one
+pub fn run_frames(computer: &mut Computer, script: &KeyScript, frames: Option<u64>) -> (u64, Status) {
+    let mut runner = Runner::new(NullVideo, NullAudio, script);
+    runner.stop_on_halt = true;
+    runner.stop_when_idle = frames.is_none();
+    runner.frames = frames;
+
+    // none of the headless parts can fail
+    let status = runner.run(computer).unwrap_or(Status::Running);
+    (runner.frame(), status)
+}
+
+pub fn screen_png(computer: &Computer) -> Vec<u8> {
+    let pixels: Vec<u8> = computer.display.memory.iter().map(|pixel| if *pixel != 0 { 255 } else { 0 }).collect();
+    png::encode_grayscale(computer.display.width() as u32, computer.display.height() as u32, &pixels)
+}
+
+pub fn to_json(computer: &Computer, rom_name: &str, frames: u64, status: &Status) -> String {
+    let cpu = &computer.cpu;
+    let status = match status {
+        Status::Running => String::from("running"),
+        Status::WaitingKey => String::from("waiting_key"),
+        Status::Paused => String::from("paused"),
+        Status::Halted(reason) => format!("halted: {reason}"),
+        Status::Fault(fault) => format!("fault: {fault}"),
+    };
+    let regs: Vec<String> = cpu.regs.iter().map(|value| value.to_string()).collect();
+    let screen: Vec<String> = computer.display.to_ascii().lines().map(|line| format!("\"{line}\"")).collect();
+
+    format!(
+        "{{\n  \"rom\": \"{}\",\n  \"frames\": {},\n  \"cycles\": {},\n  \"status\": \"{}\",\n  \"registers\": {{\"v\": [{}], \"i\": {}, \"pc\": {}, \"sp\": {}, \"dt\": {}, \"st\": {}}},\n  \"memory_checksum\": \"{:08x}\",\n  \"screen\": [\n    {}\n  ]\n}}\n",
+        json_escape(rom_name),
+        frames,
+        computer.cycles,
+        json_escape(&status),
+        regs.join(", "),
+        cpu.i_reg,
+        cpu.pc,
+        cpu.sp,
+        computer.delay_timer,
+        computer.sound_timer,
+        png::crc32(&cpu.bus.memory),
+        screen.join(",\n    ")
+    )
+}
+
+// ROM paths may hold any characters, control characters aren't allowed in JSON strings
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(rom: Vec<u8>) -> Computer {
+        let mut computer = Computer::new();
+        computer.reset();
+        computer.load_rom(rom).unwrap();
+        computer
+    }
+
+    #[test]
+    fn stops_waiting_for_keys_the_script_never_presses() {
+        let rom = crate::chip8! {
+            ld v0, k;
+            ld v1, k;
+            end: jp end
+        };
+
+        let script = KeyScript::parse("2 a press").unwrap();
+        let mut computer = load(rom.clone());
+        assert_eq!(run_frames(&mut computer, &script, None), (4, Status::WaitingKey));
+        assert_eq!(computer.cpu.regs[0], 0xA);
+
+        // with a frame count the run goes on, keys may come from elsewhere
+        let mut computer = load(rom);
+        assert_eq!(run_frames(&mut computer, &script, Some(10)), (10, Status::WaitingKey));
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_escape("roms/a \"b\"\\c.ch8"), "roms/a \\\"b\\\"\\\\c.ch8");
+        assert_eq!(json_escape("a\nb\r\tc\u{1}\u{1f}"), "a\\nb\\r\\tc\\u0001\\u001f");
+        assert_eq!(json_escape("caf\u{e9}"), "caf\u{e9}");
+    }
+
+    #[test]
+    fn reports_final_state_as_json() {
+        let mut computer = load(crate::chip8! {
+            ld v0, 5;
+            ld i, 0x300;
+            drw v1, v1, 1;
+            end: jp end
+        });
+        let (frames, status) = run_frames(&mut computer, &KeyScript { events: Vec::new() }, None);
+
+        let json = to_json(&computer, "roms/new\nline.ch8", frames, &status);
+        assert!(json.starts_with("{\n  \"rom\": \"roms/new\\nline.ch8\",\n"));
+        assert!(json.contains("\"status\": \"halted: jump to itself at 0x206\""));
+        assert!(json.contains("\"registers\": {\"v\": [5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], \"i\": 768, \"pc\": 518,"));
+        assert!(json.contains(&format!("\"memory_checksum\": \"{:08x}\"", png::crc32(&computer.cpu.bus.memory))));
+        // the sprite row at 0x300 is empty, nothing is drawn
+        assert!(json.contains(&format!("  \"screen\": [\n    \"{}\",\n", ".".repeat(64))));
+        assert_eq!(json.matches(&".".repeat(64)).count(), 32);
+    }
+
+    #[test]
+    fn screen_png_has_display_size() {
+        let mut computer = load(crate::chip8! {
+            high;
+            end: jp end
+        });
+        run_frames(&mut computer, &KeyScript { events: Vec::new() }, None);
+        computer.display.memory[0] = 1;
+
+        let png = screen_png(&computer);
+        assert_eq!(png[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+        // IHDR width and height
+        assert_eq!(png[16..24], [0, 0, 0, 128, 0, 0, 0, 64]);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod computer;
+pub mod debug_panel;
+pub mod frontend;
+pub mod fuzz;
+pub mod golden;
+pub mod headless;
+pub mod options;
+pub mod palette;
+pub mod persistence;
+pub mod renderer;
+pub mod scheduler;
+pub mod trace_diff;
+#[cfg(unix)]
+pub mod tui;
+pub mod upscale;
+pub mod utils;
@@ -1,130 +1,165 @@
 
 extern crate sdl2;
 
-use std::time::{Duration, Instant};
+use crab8::{golden, headless, trace_diff, utils};
+use crab8::computer::{Computer, Status};
+use crab8::computer::coverage::{Coverage, LineMap};
+use crab8::computer::fault::crash_report;
+use crab8::computer::profiler::Profiler;
+use crab8::computer::sanitizer::Sanitizer;
+use crab8::computer::smc::SmcDetector;
+use crab8::computer::trace::Tracer;
+use crab8::frontend::sdl;
+use crab8::options::Options;
+#[cfg(unix)]
+use crab8::tui;
 
-pub mod computer;
-pub mod utils;
+pub fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
 
-use crate::computer::Computer;
-use crate::computer::display::WIDTH as DISPLAY_WIDTH;
-use crate::computer::display::HEIGHT as DISPLAY_HEIGHT;
+    if args.get(1).is_some_and(|command| command == "trace-diff") {
+        if !trace_diff::run(&args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
+    if args.get(1).is_some_and(|command| command == "golden") {
+        if !golden::run(&args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-const SCALE_FACTOR: f32 = 10.0;
+    // `crab8 run rom.ch8` is the same as `crab8 rom.ch8`
+    let args: Vec<String> = match args.get(1) {
+        Some(command) if command == "run" => [&args[..1], &args[2..]].concat(),
+        _ => args,
+    };
 
-pub fn main() -> Result<(), String> {
+    let mut options = Options::parse(&args)?;
+    let (mut computer, line_map) = setup(&mut options)?;
+
+    let status = if options.headless {
+        headless::run(&mut computer, &options)?
+    } else if options.tui {
+        run_tui(&mut computer, &options)?
+    } else {
+        sdl::run(&mut computer, &options)?
+    };
+
+    finish(&mut computer, &options, line_map.as_ref(), status)
+}
+
+#[cfg(unix)]
+fn run_tui(computer: &mut Computer, options: &Options) -> Result<Status, String> {
+    tui::run(computer, options)
+}
+
+// Raw terminal mode is only implemented with termios
+#[cfg(not(unix))]
+fn run_tui(_computer: &mut Computer, _options: &Options) -> Result<Status, String> {
+    Err(String::from("--tui is only supported on Unix terminals"))
+}
+
+// Creates the computer with the ROM loaded and analysis tools attached
+fn setup(options: &mut Options) -> Result<(Computer, Option<LineMap>), String> {
     // init Computer
     let mut computer = Computer::new();
     computer.reset();
 
     // load ROM
-    let args: Vec<String> = std::env::args().collect();
-    let rom_name = match args.get(1) {
-        Some(value) => value.as_str(),
-        None => "IBM"
-    };
-    let rom_data = utils::load_rom(rom_name).unwrap();
-    computer.load_rom(rom_data);
-
-    // init SDL
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-    let window_width = DISPLAY_WIDTH as u32 * SCALE_FACTOR as u32;
-    let window_height = DISPLAY_HEIGHT as u32 * SCALE_FACTOR as u32;
-
-    let window = video_subsystem
-        .window("CRAB-8", window_width, window_height)
-        .position_centered()
-        .opengl()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
-
-    canvas.set_scale(SCALE_FACTOR, SCALE_FACTOR).unwrap();
-    canvas.set_draw_color(Color::BLACK);
-    canvas.clear();
-    canvas.present();
-
-    let mut last_time = Instant::now();
-
-    let mut event_pump = sdl_context.event_pump()?;
-
-    'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                Event::KeyDown { keycode, .. } => {
-                    computer.register_key_event(keycode.unwrap(), true);
-                },
-                Event::KeyUp { keycode, .. } => {
-                    computer.register_key_event(keycode.unwrap(), false);
-                },
-                _ => {}
-            }
-        }
+    let rom_data = utils::load_rom(&options.rom_name).map_err(|e| format!("Unable to load ROM {}: {e}", options.rom_name))?;
+    let rom_size = rom_data.len();
+    computer.load_rom(rom_data)?;
+    if let Some(seed) = options.seed {
+        computer.cpu.seed(seed);
+    }
+    if let Some(platform) = options.platform {
+        computer.cpu.quirks = platform.quirks();
+    }
 
-        if !computer.waiting_key {
-            computer.emulate_cycle();
-        } else {
-            println!("waiting key...");
-        }
+    for watchpoint in options.watchpoints.drain(..) {
+        computer.cpu.bus.add_watchpoint(watchpoint);
+    }
+    for breakpoint in options.breakpoints.drain(..) {
+        computer.debugger.add_breakpoint(breakpoint);
+    }
 
-        if computer.should_redraw {
-            canvas.set_draw_color(Color::BLACK);
-            canvas.clear();
-            canvas.set_draw_color(Color::WHITE);
-            let mut x_pos: i32 = 0;
-            let mut y_pos: i32 = 0;
-            let display_divisor: i32 = (DISPLAY_WIDTH - 1).into();
-
-            for pixel_data in computer.display.memory {
-                if pixel_data != 0 {
-                    canvas.draw_point(Point::new(x_pos, y_pos)).unwrap();
-                }
-
-                // calculate Y coordinate in linear array of pixels
-                if x_pos != 0 && (x_pos % display_divisor as i32) == 0 {
-                    x_pos = 0;
-                    y_pos += 1;
-                } else {
-                    x_pos += 1;
-                }
-            }
-
-            canvas.present();
-            computer.should_redraw = false;
-        }
+    if let Some(trace_path) = &options.trace_path {
+        let tracer = Tracer::create(trace_path, options.trace_format, options.trace_filter.clone())
+            .map_err(|e| format!("Unable to create trace file {trace_path}: {e}"))?;
+        computer.tracer = Some(tracer);
+    }
+
+    if options.profile || options.profile_json_path.is_some() {
+        computer.profiler = Some(Profiler::new());
+    }
+
+    // line map is loaded early, so a broken file is reported before the run
+    let line_map = options.line_map_path.as_deref().map(LineMap::load).transpose()?;
+    if options.coverage_path.is_some() || options.lcov_path.is_some() {
+        computer.coverage = Some(Coverage::new(rom_size));
+    }
+
+    if options.sanitize {
+        computer.sanitizer = Some(Sanitizer::new(rom_size));
+    }
+
+    computer.halt_detector.detect_loops = options.detect_loops;
+
+    if options.smc || options.smc_break {
+        computer.smc = Some(SmcDetector::new(options.smc_break));
+    }
 
-        if computer.should_clear_screen {
-            canvas.set_draw_color(Color::BLACK);
-            canvas.clear();
-            canvas.present();
-            computer.should_clear_screen = false;
+    Ok((computer, line_map))
+}
+
+// Writes reports of the attached tools, and the crash report on faults
+fn finish(computer: &mut Computer, options: &Options, line_map: Option<&LineMap>, status: Status) -> Result<(), String> {
+
+    if let Some(tracer) = computer.tracer.as_mut() {
+        tracer.flush().map_err(|e| e.to_string())?;
+    }
+
+    if let Some(profiler) = &computer.profiler {
+        if options.profile {
+            print!("{}", profiler.report(&computer.cpu.bus.memory));
+        }
+        if let Some(path) = &options.profile_json_path {
+            std::fs::write(path, profiler.to_json())
+                .map_err(|e| format!("Unable to write profile {path}: {e}"))?;
         }
+    }
 
-        if last_time.elapsed() >= Duration::from_millis(1000 / 60) {
-            if computer.delay_timer > 0 {
-                computer.delay_timer -= 1;
-            }
-            if computer.sound_timer > 0 {
-                computer.sound_timer -= 1;
-            }
+    if let Some(sanitizer) = &computer.sanitizer {
+        println!("Sanitizer: {} uninitialized reads found", sanitizer.reports.len());
+    }
+
+    if let Some(smc) = &computer.smc {
+        print!("{}", smc.report());
+    }
 
-            last_time = Instant::now();
+    if let Some(coverage) = &computer.coverage {
+        if let Some(path) = &options.coverage_path {
+            std::fs::write(path, coverage.annotated_disassembly(&computer.cpu.bus.memory))
+                .map_err(|e| format!("Unable to write coverage {path}: {e}"))?;
         }
+        if let (Some(path), Some(line_map)) = (&options.lcov_path, line_map) {
+            std::fs::write(path, coverage.lcov(line_map))
+                .map_err(|e| format!("Unable to write lcov report {path}: {e}"))?;
+        }
+    }
 
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 2564));
+    if let Status::Fault(fault) = status {
+        let report = crash_report(computer, &fault);
+        std::fs::write(&options.crash_dump_path, report)
+            .map_err(|e| format!("Unable to write crash report {}: {e}", options.crash_dump_path))?;
+        return Err(format!(
+            "Emulation fault: {fault} at {:#05x}, crash report written to {}",
+            computer.cpu.pc, options.crash_dump_path
+        ));
     }
 
     Ok(())
-}
\ No newline at end of file
+}
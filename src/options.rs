@@ -0,0 +1,237 @@
+use crate::computer::bus::Watchpoint;
+use crate::computer::debugger::Breakpoint;
+use crate::computer::quirks::Platform;
+use crate::computer::trace::{TraceFilter, TraceFormat};
+use crate::palette::{Palette, Rgb};
+use crate::persistence::Mode as PersistenceMode;
+use crate::upscale::Filter;
+
+pub struct Options {
+    pub rom_name: String,
+    // Memory watchpoints, e.g. `--watch w:0x300-0x30f`
+    pub watchpoints: Vec<Watchpoint>,
+    // Breakpoints and tracepoints, e.g. `--break "0x2a4 if v3 > 10"`
+    pub breakpoints: Vec<Breakpoint>,
+    // Execution trace file, e.g. `--trace run.log`
+    pub trace_path: Option<String>,
+    // `--trace-format text|binary`
+    pub trace_format: TraceFormat,
+    // `--trace-pc 0x200-0x2ff` and `--trace-cycles 1000-2000`
+    pub trace_filter: TraceFilter,
+    // Print hotspot report on exit
+    pub profile: bool,
+    // Write profile as JSON on exit, e.g. `--profile-json profile.json`
+    pub profile_json_path: Option<String>,
+    // Write annotated ROM disassembly on exit, e.g. `--coverage coverage.txt`
+    pub coverage_path: Option<String>,
+    // Source line-map of the ROM, required for the lcov report
+    pub line_map_path: Option<String>,
+    // Write lcov report on exit, e.g. `--lcov coverage.info`
+    pub lcov_path: Option<String>,
+    // Report reads of registers and memory which were never written
+    pub sanitize: bool,
+    // Report writes into executed instructions
+    pub smc: bool,
+    // Pause on writes into executed instructions
+    pub smc_break: bool,
+    // Detect loops without state changes, not only jumps to itself
+    pub detect_loops: bool,
+    // Quit once the program halts
+    pub exit_on_halt: bool,
+    // Where the crash report is written on emulation faults
+    pub crash_dump_path: String,
+    // Run without a window, see `headless.rs`
+    pub headless: bool,
+    // Run in the terminal, see `tui.rs`
+    pub tui: bool,
+    // Frames to run headless, until halt or fault when not set
+    pub frames: Option<u64>,
+    // Scripted key input, e.g. `--keys "10 5 press,20 a down"` or a file path
+    pub keys: Option<String>,
+    // Write final screen as PNG, e.g. `--png screen.png`
+    pub png_path: Option<String>,
+    // Seed of the random number generator, e.g. `--seed 42`
+    pub seed: Option<u64>,
+    // Quirks of the emulated platform, e.g. `--platform schip`, CRAB8's own behaviour when not set
+    pub platform: Option<Platform>,
+    // Display colours, e.g. `--palette amber` or `--palette 000000,ffffff`
+    pub palette: Palette,
+    // Draw lines between pixels
+    pub grid: bool,
+    // Fading of unlit pixels against flicker, e.g. `--persistence fade:0.3` or `--persistence max:3`
+    pub persistence: PersistenceMode,
+    // Initial window size in pixels per CHIP-8 pixel, e.g. `--scale 8`
+    pub scale: u32,
+    // Scale the display only by whole numbers when the window is resized
+    pub integer_scale: bool,
+    // Upscaling filter, e.g. `--filter scale2x`
+    pub filter: Filter,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            rom_name: String::from("IBM"),
+            watchpoints: Vec::new(),
+            breakpoints: Vec::new(),
+            trace_path: None,
+            trace_format: TraceFormat::Text,
+            trace_filter: TraceFilter::default(),
+            profile: false,
+            profile_json_path: None,
+            coverage_path: None,
+            line_map_path: None,
+            lcov_path: None,
+            sanitize: false,
+            smc: false,
+            smc_break: false,
+            detect_loops: false,
+            exit_on_halt: false,
+            crash_dump_path: String::from("crab8-crash.txt"),
+            headless: false,
+            tui: false,
+            frames: None,
+            keys: None,
+            png_path: None,
+            seed: None,
+            platform: None,
+            palette: Palette::default(),
+            grid: false,
+            persistence: PersistenceMode::Off,
+            scale: 10,
+            integer_scale: false,
+            filter: Filter::None,
+        };
+        // applied over the palette once all options are read
+        let mut foreground = None;
+        let mut background = None;
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--watch" => {
+                    let value = args.next().ok_or("--watch requires a value")?;
+                    options.watchpoints.push(Watchpoint::parse(value)?);
+                },
+                "--break" => {
+                    let value = args.next().ok_or("--break requires a value")?;
+                    options.breakpoints.push(Breakpoint::parse(value)?);
+                },
+                "--tracepoint" => {
+                    let value = args.next().ok_or("--tracepoint requires a value")?;
+                    options.breakpoints.push(Breakpoint::parse_tracepoint(value)?);
+                },
+                "--trace" => {
+                    let value = args.next().ok_or("--trace requires a file path")?;
+                    options.trace_path = Some(value.clone());
+                },
+                "--trace-format" => {
+                    let value = args.next().ok_or("--trace-format requires a value")?;
+                    options.trace_format = TraceFormat::parse(value)?;
+                },
+                "--trace-pc" => {
+                    let value = args.next().ok_or("--trace-pc requires an address range")?;
+                    options.trace_filter.pc_range = Some(TraceFilter::parse_pc_range(value)?);
+                },
+                "--trace-cycles" => {
+                    let value = args.next().ok_or("--trace-cycles requires a cycle range")?;
+                    options.trace_filter.cycle_range = Some(TraceFilter::parse_cycle_range(value)?);
+                },
+                "--profile" => options.profile = true,
+                "--profile-json" => {
+                    let value = args.next().ok_or("--profile-json requires a file path")?;
+                    options.profile_json_path = Some(value.clone());
+                },
+                "--coverage" => {
+                    let value = args.next().ok_or("--coverage requires a file path")?;
+                    options.coverage_path = Some(value.clone());
+                },
+                "--line-map" => {
+                    let value = args.next().ok_or("--line-map requires a file path")?;
+                    options.line_map_path = Some(value.clone());
+                },
+                "--lcov" => {
+                    let value = args.next().ok_or("--lcov requires a file path")?;
+                    options.lcov_path = Some(value.clone());
+                },
+                "--sanitize" => options.sanitize = true,
+                "--smc" => options.smc = true,
+                "--smc-break" => options.smc_break = true,
+                "--detect-loops" => options.detect_loops = true,
+                "--exit-on-halt" => options.exit_on_halt = true,
+                "--crash-dump" => {
+                    let value = args.next().ok_or("--crash-dump requires a file path")?;
+                    options.crash_dump_path = value.clone();
+                },
+                "--headless" => options.headless = true,
+                "--tui" => options.tui = true,
+                "--frames" => {
+                    let value = args.next().ok_or("--frames requires a number")?;
+                    let frames = value.parse().map_err(|_| format!("Invalid frame count: {value}"))?;
+                    options.frames = Some(frames);
+                },
+                "--keys" => {
+                    let value = args.next().ok_or("--keys requires a key script")?;
+                    options.keys = Some(value.clone());
+                },
+                "--png" => {
+                    let value = args.next().ok_or("--png requires a file path")?;
+                    options.png_path = Some(value.clone());
+                },
+                "--seed" => {
+                    let value = args.next().ok_or("--seed requires a number")?;
+                    let seed = value.parse().map_err(|_| format!("Invalid seed: {value}"))?;
+                    options.seed = Some(seed);
+                },
+                "--platform" => {
+                    let value = args.next().ok_or("--platform requires a value")?;
+                    options.platform = Some(Platform::parse(value)?);
+                },
+                "--palette" => {
+                    let value = args.next().ok_or("--palette requires a theme, colours or a file path")?;
+                    options.palette = Palette::parse(value)?;
+                },
+                "--fg" => {
+                    let value = args.next().ok_or("--fg requires a colour")?;
+                    foreground = Some(Rgb::parse(value)?);
+                },
+                "--bg" => {
+                    let value = args.next().ok_or("--bg requires a colour")?;
+                    background = Some(Rgb::parse(value)?);
+                },
+                "--grid" => options.grid = true,
+                "--scale" => {
+                    let value = args.next().ok_or("--scale requires a number")?;
+                    options.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale| *scale > 0)
+                        .ok_or(format!("Invalid scale: {value}"))?;
+                },
+                "--integer-scale" => options.integer_scale = true,
+                "--filter" => {
+                    let value = args.next().ok_or("--filter requires a value")?;
+                    options.filter = Filter::parse(value)?;
+                },
+                "--persistence" => {
+                    let value = args.next().ok_or("--persistence requires a mode")?;
+                    options.persistence = PersistenceMode::parse(value)?;
+                },
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+                _ => options.rom_name = arg.clone(),
+            }
+        }
+
+        if foreground.is_some() || background.is_some() {
+            options.palette.colors[0] = background.unwrap_or(options.palette.background());
+            options.palette.colors[1] = foreground.unwrap_or(options.palette.foreground());
+            options.palette.name = String::from("custom");
+        }
+
+        if options.lcov_path.is_some() && options.line_map_path.is_none() {
+            return Err(String::from("--lcov requires --line-map"));
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(value: u32) -> Rgb {
+        Rgb { r: (value >> 16) as u8, g: (value >> 8) as u8, b: value as u8 }
+    }
+
+    // Parses `RRGGBB` or `#RRGGBB`
+    pub fn parse(text: &str) -> Result<Rgb, String> {
+        let hex = text.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return Err(format!("Invalid colour: {text}, expected RRGGBB"));
+        }
+        let value = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid colour: {text}, expected RRGGBB"))?;
+        Ok(Rgb::new(value))
+    }
+
+    // Moves the colour towards `other` by `amount` out of 255
+    pub fn mix(&self, other: Rgb, amount: u8) -> Rgb {
+        let channel = |from: u8, to: u8| ((from as u32 * (255 - amount as u32) + to as u32 * amount as u32) / 255) as u8;
+        Rgb { r: channel(self.r, other.r), g: channel(self.g, other.g), b: channel(self.b, other.b) }
+    }
+}
+
+// Colours of the pixel values, index 0 is the background and 1 the foreground,
+// 2 and 3 are the XO-CHIP second plane and both planes lit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Rgb; 4],
+}
+
+// name, background, foreground, second plane, both planes
+const THEMES: [(&str, [u32; 4]); 5] = [
+    ("high-contrast", [0x000000, 0xFFFFFF, 0xFF0000, 0x00FFFF]),
+    ("green", [0x0A1A0A, 0x33FF66, 0x1A8033, 0xB3FFCC]),
+    ("amber", [0x1A0F00, 0xFFB000, 0x805800, 0xFFE0A0]),
+    ("lcd", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ("octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+];
+
+impl Palette {
+    pub fn themes() -> Vec<Palette> {
+        THEMES
+            .iter()
+            .map(|(name, colors)| Palette { name: name.to_string(), colors: colors.map(Rgb::new) })
+            .collect()
+    }
+
+    pub fn theme(name: &str) -> Option<Palette> {
+        Palette::themes().into_iter().find(|palette| palette.name == name)
+    }
+
+    // `spec` is a theme name, a file path or inline colours: `BG,FG` or `BG,FG,PLANE2,BOTH`,
+    // in files colours can be on separate lines and `;` starts a comment
+    pub fn parse(spec: &str) -> Result<Palette, String> {
+        if let Some(palette) = Palette::theme(spec) {
+            return Ok(palette);
+        }
+
+        let text = if Path::new(spec).is_file() {
+            std::fs::read_to_string(spec).map_err(|e| format!("Unable to read palette {spec}: {e}"))?
+        } else {
+            spec.to_string()
+        };
+
+        let colors = text
+            .lines()
+            .map(|line| line.split(';').next().unwrap_or(""))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|color| !color.is_empty())
+            .map(Rgb::parse)
+            .collect::<Result<Vec<Rgb>, String>>()?;
+
+        let (background, foreground) = match colors[..] {
+            [background, foreground] | [background, foreground, _, _] => (background, foreground),
+            _ => {
+                let names: Vec<String> = Palette::themes().into_iter().map(|palette| palette.name).collect();
+                return Err(format!(
+                    "Invalid palette: {spec}, expected one of {} or 2 or 4 colours",
+                    names.join(", ")
+                ));
+            },
+        };
+
+        // two-colour palettes show the XO-CHIP planes in the foreground colour
+        let plane = colors.get(2).copied().unwrap_or(foreground);
+        let both = colors.get(3).copied().unwrap_or(foreground);
+        Ok(Palette { name: String::from("custom"), colors: [background, foreground, plane, both] })
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> Rgb {
+        self.colors[1]
+    }
+
+    // Colour of a display pixel value
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[pixel as usize & 3]
+    }
+
+    // Grid lines are a faint foreground over the background
+    pub fn grid(&self) -> Rgb {
+        self.background().mix(self.foreground(), 48)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::themes().remove(0)
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Palettes cycled through at runtime, the selected one first
+pub fn cycle_list(selected: Palette) -> Vec<Palette> {
+    let mut palettes = vec![selected.clone()];
+    palettes.extend(Palette::themes().into_iter().filter(|palette| *palette != selected));
+    palettes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_themes_and_colours() {
+        assert_eq!(Palette::parse("amber").unwrap().foreground(), Rgb::new(0xFFB000));
+
+        let palette = Palette::parse("#102030, 405060").unwrap();
+        assert_eq!(palette.background(), Rgb { r: 0x10, g: 0x20, b: 0x30 });
+        assert_eq!(palette.color(3), Rgb::new(0x405060));
+
+        let palette = Palette::parse("000000,111111,222222,333333").unwrap();
+        assert_eq!(palette.color(2), Rgb::new(0x222222));
+
+        assert!(Palette::parse("000000").is_err());
+        assert!(Palette::parse("000000,12345g").is_err());
+        assert!(Palette::parse("sepia").is_err());
+    }
+
+    #[test]
+    fn cycle_list_starts_with_selected() {
+        let palettes = cycle_list(Palette::theme("lcd").unwrap());
+        assert_eq!(palettes.len(), THEMES.len());
+        assert_eq!(palettes[0].name, "lcd");
+    }
+}
@@ -0,0 +1,159 @@
+// Phosphor persistence: games erase sprites by drawing them again, so without it
+// moving sprites flicker. Pixels turned off either fade out or stay lit for a few frames.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+pub const DEFAULT_DECAY: f32 = 0.5;
+pub const DEFAULT_FRAMES: usize = 2;
+const MAX_LEVEL: u8 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Off,
+    // Unlit pixels lose `decay` of their brightness every frame
+    Fade(f32),
+    // Pixels lit in any of the last N frames are shown
+    Max(usize),
+}
+
+impl Mode {
+    // Parses `off`, `fade[:DECAY]` or `max[:FRAMES]`, e.g. `fade:0.3`
+    pub fn parse(text: &str) -> Result<Mode, String> {
+        let (name, value) = match text.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (text, None),
+        };
+
+        match (name, value) {
+            ("off", None) => Ok(Mode::Off),
+            ("fade", None) => Ok(Mode::Fade(DEFAULT_DECAY)),
+            ("fade", Some(value)) => value
+                .parse()
+                .ok()
+                .filter(|decay| *decay > 0.0 && *decay <= 1.0)
+                .map(Mode::Fade)
+                .ok_or(format!("Invalid decay: {value}, expected a number in 0..1")),
+            ("max", None) => Ok(Mode::Max(DEFAULT_FRAMES)),
+            ("max", Some(value)) => value
+                .parse()
+                .ok()
+                .filter(|frames| *frames > 0)
+                .map(Mode::Max)
+                .ok_or(format!("Invalid frame count: {value}")),
+            _ => Err(format!("Unknown persistence mode: {text}, expected off, fade[:DECAY] or max[:FRAMES]")),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Off => write!(f, "off"),
+            Mode::Fade(decay) => write!(f, "fade:{decay}"),
+            Mode::Max(frames) => write!(f, "max:{frames}"),
+        }
+    }
+}
+
+// Filters display frames, the result is a colour index and brightness per pixel
+pub struct Persistence {
+    pub mode: Mode,
+    // Pixel values as last seen lit, so fading pixels keep their colour
+    pub values: Vec<u8>,
+    // Brightness, MAX_LEVEL for lit pixels
+    pub levels: Vec<u8>,
+    // Last frames in Max mode, newest first
+    history: VecDeque<Vec<u8>>,
+}
+
+impl Persistence {
+    pub fn new(mode: Mode, size: usize) -> Persistence {
+        Persistence {
+            mode,
+            values: vec![0; size],
+            levels: vec![0; size],
+            history: VecDeque::new(),
+        }
+    }
+
+    // Adds the next frame, called once per 60Hz frame
+    pub fn update(&mut self, screen: &[u8]) {
+        // a resolution switch starts over with the new size
+        if screen.len() != self.values.len() {
+            *self = Persistence::new(self.mode, screen.len());
+        }
+
+        match self.mode {
+            Mode::Off => {
+                for (index, pixel) in screen.iter().enumerate() {
+                    self.values[index] = *pixel;
+                    self.levels[index] = if *pixel != 0 { MAX_LEVEL } else { 0 };
+                }
+            },
+            Mode::Fade(decay) => {
+                for (index, pixel) in screen.iter().enumerate() {
+                    if *pixel != 0 {
+                        self.values[index] = *pixel;
+                        self.levels[index] = MAX_LEVEL;
+                    } else {
+                        self.levels[index] = (self.levels[index] as f32 * (1.0 - decay)) as u8;
+                    }
+                }
+            },
+            Mode::Max(frames) => {
+                self.history.push_front(screen.to_vec());
+                self.history.truncate(frames);
+                for index in 0..screen.len() {
+                    let lit = self.history.iter().map(|frame| frame[index]).find(|pixel| *pixel != 0);
+                    self.values[index] = lit.unwrap_or(0);
+                    self.levels[index] = if lit.is_some() { MAX_LEVEL } else { 0 };
+                }
+            },
+        }
+    }
+
+    // Switches mode, dropping the state of the previous one
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.history.clear();
+        self.levels.iter_mut().for_each(|level| *level = 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(Mode::parse("off").unwrap(), Mode::Off);
+        assert_eq!(Mode::parse("fade").unwrap(), Mode::Fade(DEFAULT_DECAY));
+        assert_eq!(Mode::parse("fade:0.25").unwrap(), Mode::Fade(0.25));
+        assert_eq!(Mode::parse("max:3").unwrap(), Mode::Max(3));
+        assert!(Mode::parse("fade:2").is_err());
+        assert!(Mode::parse("max:0").is_err());
+        assert!(Mode::parse("blur").is_err());
+    }
+
+    #[test]
+    fn fades_unlit_pixels() {
+        let mut persistence = Persistence::new(Mode::Fade(0.5), 2);
+        persistence.update(&[1, 0]);
+        persistence.update(&[0, 0]);
+        assert_eq!(persistence.values, [1, 0]);
+        assert_eq!(persistence.levels, [127, 0]);
+        persistence.update(&[0, 0]);
+        assert_eq!(persistence.levels, [63, 0]);
+    }
+
+    #[test]
+    fn max_keeps_pixels_lit_in_last_frames() {
+        let mut persistence = Persistence::new(Mode::Max(2), 2);
+        persistence.update(&[1, 0]);
+        persistence.update(&[0, 1]);
+        assert_eq!(persistence.levels, [255, 255]);
+        persistence.update(&[0, 0]);
+        assert_eq!(persistence.levels, [0, 255]);
+    }
+}
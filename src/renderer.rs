@@ -0,0 +1,168 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+use crate::computer::display::Display;
+use crate::computer::display::WIDTH as DISPLAY_WIDTH;
+use crate::computer::display::HEIGHT as DISPLAY_HEIGHT;
+use crate::palette::{Palette, Rgb};
+use crate::persistence::{Mode, Persistence};
+use crate::upscale::Filter;
+
+const BYTES_PER_PIXEL: usize = 3;
+
+// Uploads the framebuffer into a streaming texture, which is scaled by the renderer
+pub struct Renderer<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    // Display size and filter the texture was created for, its size depends on both
+    texture_size: (u32, u32),
+    texture_filter: Filter,
+    // RGB24 copy of the upscaled display
+    pixels: Vec<u8>,
+    pub palette: Palette,
+    // Draw lines between pixels
+    pub grid: bool,
+    pub persistence: Persistence,
+    pub filter: Filter,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, palette: Palette) -> Result<Renderer<'a>, String> {
+        let texture_size = (DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32);
+        Ok(Renderer {
+            texture_creator,
+            texture: create_texture(texture_creator, texture_size, Filter::None)?,
+            texture_size,
+            texture_filter: Filter::None,
+            pixels: Vec::new(),
+            palette,
+            grid: false,
+            persistence: Persistence::new(Mode::Off, DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize),
+            filter: Filter::None,
+        })
+    }
+
+    // Copies the display into the texture, called once per frame
+    pub fn update(&mut self, display: &Display) -> Result<(), String> {
+        self.persistence.update(&display.memory);
+        let background = self.palette.background();
+        let shades = self.persistence.values.iter().zip(&self.persistence.levels);
+        let screen: Vec<Rgb> = shades
+            .map(|(value, level)| background.mix(self.palette.color(*value), *level))
+            .collect();
+
+        // the display may switch resolution while running
+        let (width, height) = (display.width(), display.height());
+        if self.filter != self.texture_filter || self.texture_size != (width as u32, height as u32) {
+            self.texture_size = (width as u32, height as u32);
+            self.texture = create_texture(self.texture_creator, self.texture_size, self.filter)?;
+            self.texture_filter = self.filter;
+        }
+
+        let upscaled = self.filter.apply(&screen, width, height);
+        self.pixels.clear();
+        self.pixels.extend(upscaled.iter().flat_map(|color| [color.r, color.g, color.b]));
+
+        let pitch = width * self.filter.factor() * BYTES_PER_PIXEL;
+        self.texture.update(None, &self.pixels, pitch).map_err(|e| e.to_string())
+    }
+
+    // Size of the display in the last update
+    pub fn display_size(&self) -> (u32, u32) {
+        self.texture_size
+    }
+
+    // Draws the texture stretched over `target`
+    pub fn draw(&self, canvas: &mut Canvas<Window>, target: Rect) -> Result<(), String> {
+        canvas.copy(&self.texture, None, Some(target))?;
+
+        if self.grid {
+            self.draw_grid(canvas, target)?;
+        }
+        Ok(())
+    }
+
+    fn draw_grid(&self, canvas: &mut Canvas<Window>, target: Rect) -> Result<(), String> {
+        canvas.set_draw_color(to_color(self.palette.grid()));
+
+        let (width, height) = (self.texture_size.0 as i32, self.texture_size.1 as i32);
+        for column in 1..width {
+            let x = target.x() + column * target.width() as i32 / width;
+            canvas.draw_line((x, target.top()), (x, target.bottom() - 1))?;
+        }
+        for row in 1..height {
+            let y = target.y() + row * target.height() as i32 / height;
+            canvas.draw_line((target.left(), y), (target.right() - 1, y))?;
+        }
+        Ok(())
+    }
+}
+
+fn create_texture(
+    texture_creator: &TextureCreator<WindowContext>,
+    (width, height): (u32, u32),
+    filter: Filter,
+) -> Result<Texture<'_>, String> {
+    let factor = filter.factor() as u32;
+    texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, width * factor, height * factor)
+        .map_err(|e| e.to_string())
+}
+
+// Largest rect with the display's aspect ratio centered in the area, the rest is letterboxed;
+// with `integer_scaling` pixels are only scaled by whole numbers
+pub fn fit_display(
+    area_width: u32,
+    area_height: u32,
+    (display_width, display_height): (u32, u32),
+    integer_scaling: bool,
+) -> Rect {
+
+    let (width, height) = if integer_scaling {
+        let scale = (area_width / display_width).min(area_height / display_height).max(1);
+        (display_width * scale, display_height * scale)
+    } else if area_width * display_height > area_height * display_width {
+        (area_height * display_width / display_height, area_height)
+    } else {
+        (area_width, area_width * display_height / display_width)
+    };
+
+    let x = (area_width as i32 - width as i32) / 2;
+    let y = (area_height as i32 - height as i32) / 2;
+    Rect::new(x, y, width.max(1), height.max(1))
+}
+
+pub fn to_color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.r, rgb.g, rgb.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LORES: (u32, u32) = (64, 32);
+    const HIRES: (u32, u32) = (128, 64);
+
+    #[test]
+    fn fit_display_letterboxes() {
+        assert_eq!(fit_display(640, 320, LORES, false), Rect::new(0, 0, 640, 320));
+        assert_eq!(fit_display(800, 320, LORES, false), Rect::new(80, 0, 640, 320));
+        assert_eq!(fit_display(640, 400, LORES, false), Rect::new(0, 40, 640, 320));
+    }
+
+    #[test]
+    fn fit_display_integer_scaling() {
+        assert_eq!(fit_display(700, 400, LORES, false), Rect::new(0, 25, 700, 350));
+        assert_eq!(fit_display(700, 400, LORES, true), Rect::new(30, 40, 640, 320));
+        assert_eq!(fit_display(10, 10, LORES, true), Rect::new(-27, -11, 64, 32));
+    }
+
+    #[test]
+    fn fit_display_follows_display_size() {
+        assert_eq!(fit_display(640, 400, HIRES, false), Rect::new(0, 40, 640, 320));
+        assert_eq!(fit_display(700, 400, HIRES, true), Rect::new(30, 40, 640, 320));
+        assert_eq!(fit_display(100, 100, HIRES, true), Rect::new(-14, 18, 128, 64));
+    }
+}
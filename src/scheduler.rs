@@ -0,0 +1,33 @@
+// Real-time pacing shared by the interactive frontends: frames, each running
+// CYCLES_PER_FRAME instructions and a timer tick, are scheduled at 60Hz
+
+use std::time::{Duration, Instant};
+
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub struct Scheduler {
+    next_frame: Instant,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler { next_frame: Instant::now() + FRAME_DURATION }
+    }
+
+    // Sleeps until the next frame is due, frames running late don't pile up
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+            self.next_frame += FRAME_DURATION;
+        } else {
+            self.next_frame = now + FRAME_DURATION;
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
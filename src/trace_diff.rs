@@ -0,0 +1,215 @@
+use crate::computer::trace::{read_trace, TraceRecord};
+
+const DEFAULT_CONTEXT: usize = 5;
+
+// First place where two traces disagree
+pub struct Divergence {
+    // Index of the divergent record in each trace, may be equal to its length
+    pub index_a: usize,
+    pub index_b: usize,
+    pub cycle: u64,
+    pub differences: Vec<String>,
+}
+
+// `crab8 trace-diff A B [--context N]`, returns true if traces match
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let mut paths = Vec::new();
+    let mut context = DEFAULT_CONTEXT;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--context" => {
+                let value = args.next().ok_or("--context requires a value")?;
+                context = value.parse().map_err(|_| format!("Invalid context: {value}"))?;
+            },
+            _ => paths.push(arg.as_str()),
+        }
+    }
+
+    let [path_a, path_b] = paths[..] else {
+        return Err(String::from("Usage: crab8 trace-diff <trace A> <trace B> [--context N]"));
+    };
+
+    let trace_a = read_trace(path_a)?;
+    let trace_b = read_trace(path_b)?;
+
+    let divergence = match find_divergence(&trace_a, &trace_b) {
+        Some(divergence) => divergence,
+        None => {
+            println!("Traces match ({} instructions)", trace_a.len());
+            return Ok(true);
+        },
+    };
+
+    println!("Traces diverge at cycle {}:", divergence.cycle);
+    for difference in divergence.differences.iter() {
+        println!("  {difference}");
+    }
+
+    println!();
+    print_context(path_a, &trace_a, divergence.index_a, context);
+    println!();
+    print_context(path_b, &trace_b, divergence.index_b, context);
+
+    Ok(false)
+}
+
+// Aligns traces by cycle number and compares records with the same cycle
+pub fn find_divergence(trace_a: &[TraceRecord], trace_b: &[TraceRecord]) -> Option<Divergence> {
+    let (mut index_a, mut index_b) = (0, 0);
+
+    loop {
+        let (record_a, record_b) = match (trace_a.get(index_a), trace_b.get(index_b)) {
+            (None, None) => return None,
+            (Some(record), None) => {
+                let difference = format!("B ends, A continues with {} more instructions", trace_a.len() - index_a);
+                return Some(Divergence { index_a, index_b, cycle: record.cycle, differences: vec![difference] });
+            },
+            (None, Some(record)) => {
+                let difference = format!("A ends, B continues with {} more instructions", trace_b.len() - index_b);
+                return Some(Divergence { index_a, index_b, cycle: record.cycle, differences: vec![difference] });
+            },
+            (Some(record_a), Some(record_b)) => (record_a, record_b),
+        };
+
+        if record_a.cycle != record_b.cycle {
+            let (cycle, trace) = if record_a.cycle < record_b.cycle {
+                (record_a.cycle, "A")
+            } else {
+                (record_b.cycle, "B")
+            };
+            let difference = format!("cycle {} is only in trace {}", cycle, trace);
+            return Some(Divergence { index_a, index_b, cycle, differences: vec![difference] });
+        }
+
+        let differences = compare_records(record_a, record_b);
+        if !differences.is_empty() {
+            return Some(Divergence { index_a, index_b, cycle: record_a.cycle, differences });
+        }
+
+        index_a += 1;
+        index_b += 1;
+    }
+}
+
+// Lists differing fields as `NAME: A != B`
+pub fn compare_records(a: &TraceRecord, b: &TraceRecord) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    if a.pc != b.pc {
+        differences.push(format!("PC: {:03x} != {:03x}", a.pc, b.pc));
+    }
+    if a.opcode != b.opcode {
+        differences.push(format!("opcode: {:04x} != {:04x}", a.opcode, b.opcode));
+    }
+    for (index, (reg_a, reg_b)) in a.regs.iter().zip(b.regs.iter()).enumerate() {
+        if reg_a != reg_b {
+            differences.push(format!("V{:X}: {:02x} != {:02x}", index, reg_a, reg_b));
+        }
+    }
+    if a.i_reg != b.i_reg {
+        differences.push(format!("I: {:03x} != {:03x}", a.i_reg, b.i_reg));
+    }
+    if a.sp != b.sp {
+        differences.push(format!("SP: {:x} != {:x}", a.sp, b.sp));
+    }
+
+    let mut addrs: Vec<u16> = a.writes.iter().chain(b.writes.iter()).map(|(addr, _)| *addr).collect();
+    addrs.sort();
+    addrs.dedup();
+
+    for addr in addrs {
+        let written_a = last_write(a, addr);
+        let written_b = last_write(b, addr);
+        if written_a != written_b {
+            differences.push(format!("memory {:03x}: {} != {}", addr, written_a, written_b));
+        }
+    }
+
+    differences
+}
+
+fn last_write(record: &TraceRecord, addr: u16) -> String {
+    record
+        .writes
+        .iter()
+        .rev()
+        .find(|(write_addr, _)| *write_addr == addr)
+        .map_or(String::from("not written"), |(_, value)| format!("{:02x}", value))
+}
+
+fn print_context(path: &str, trace: &[TraceRecord], index: usize, context: usize) {
+    println!("{path}:");
+
+    let start = index.saturating_sub(context);
+    let end = (index + context + 1).min(trace.len());
+    for (record_index, record) in trace.iter().enumerate().take(end).skip(start) {
+        let marker = if record_index == index { ">" } else { " " };
+        println!("{} {}", marker, record.to_text());
+    }
+
+    if index >= trace.len() {
+        println!("> (end of trace)");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(cycle: u64, pc: u16) -> TraceRecord {
+        TraceRecord { cycle, pc, opcode: 0x6001, regs: [0; 16], i_reg: 0, sp: 0, writes: Vec::new() }
+    }
+
+    fn trace(cycles: &[u64]) -> Vec<TraceRecord> {
+        cycles.iter().map(|cycle| record(*cycle, 0x200 + *cycle as u16 * 2)).collect()
+    }
+
+    #[test]
+    fn equal_traces_match() {
+        assert!(find_divergence(&trace(&[0, 1, 2]), &trace(&[0, 1, 2])).is_none());
+        assert!(find_divergence(&[], &[]).is_none());
+    }
+
+    #[test]
+    fn finds_first_differing_record() {
+        let trace_a = trace(&[0, 1, 2, 3]);
+        let mut trace_b = trace_a.clone();
+        trace_b[2].regs[3] = 7;
+        trace_b[2].writes.push((0x300, 1));
+        trace_b[3].pc = 0x240;
+
+        let divergence = find_divergence(&trace_a, &trace_b).unwrap();
+        assert_eq!((divergence.index_a, divergence.index_b, divergence.cycle), (2, 2, 2));
+        assert_eq!(divergence.differences, ["V3: 00 != 07", "memory 300: not written != 01"]);
+    }
+
+    #[test]
+    fn aligns_traces_by_cycle() {
+        // B is missing cycle 2, e.g. filtered out
+        let divergence = find_divergence(&trace(&[0, 1, 2, 3]), &trace(&[0, 1, 3])).unwrap();
+        assert_eq!((divergence.index_a, divergence.index_b, divergence.cycle), (2, 2, 2));
+        assert_eq!(divergence.differences, ["cycle 2 is only in trace A"]);
+    }
+
+    #[test]
+    fn reports_trace_ending_early() {
+        let divergence = find_divergence(&trace(&[0, 1]), &trace(&[0, 1, 2, 3])).unwrap();
+        assert_eq!((divergence.index_a, divergence.index_b, divergence.cycle), (2, 2, 2));
+        assert_eq!(divergence.differences, ["A ends, B continues with 2 more instructions"]);
+    }
+
+    #[test]
+    fn compares_last_write_of_each_address() {
+        let mut a = record(0, 0x200);
+        let mut b = record(0, 0x200);
+        a.writes = vec![(0x300, 1), (0x300, 2)];
+        b.writes = vec![(0x300, 2)];
+        assert!(compare_records(&a, &b).is_empty());
+
+        b.opcode = 0x6002;
+        b.i_reg = 0x300;
+        assert_eq!(compare_records(&a, &b), ["opcode: 6001 != 6002", "I: 000 != 300"]);
+    }
+}
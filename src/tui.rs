@@ -0,0 +1,341 @@
+// Terminal frontend for machines without a display: two pixels per character cell
+// drawn with the upper half block, keys are read in raw mode
+
+use std::io::{Read, Write};
+
+use crate::computer::{Computer, Status};
+use crate::computer::display::WIDTH as DISPLAY_WIDTH;
+use crate::computer::display::HEIGHT as DISPLAY_HEIGHT;
+use crate::frontend::{AudioSink, InputEvent, InputSource, Runner, VideoSink};
+use crate::options::Options;
+use crate::palette::{Palette, Rgb};
+use crate::persistence::Persistence;
+
+// Terminals report no key releases, keys stay down this many frames after the last press
+// (or the last auto-repeat of a held key)
+pub const KEY_HOLD_FRAMES: u32 = 10;
+
+const ESCAPE: u8 = 0x1B;
+const CTRL_C: u8 = 0x03;
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+// Same layout as the window: 1234 / QWER / ASDF / ZXCV
+pub fn key_index(key: u8) -> Option<usize> {
+    let index = match key.to_ascii_lowercase() {
+        b'1' => 0x1,
+        b'2' => 0x2,
+        b'3' => 0x3,
+        b'4' => 0xC,
+        b'q' => 0x4,
+        b'w' => 0x5,
+        b'e' => 0x6,
+        b'r' => 0xD,
+        b'a' => 0x7,
+        b's' => 0x8,
+        b'd' => 0x9,
+        b'f' => 0xE,
+        b'z' => 0xA,
+        b'x' => 0x0,
+        b'c' => 0xB,
+        b'v' => 0xF,
+        _ => return None,
+    };
+    Some(index)
+}
+
+// Puts the terminal into raw mode and the alternate screen, both are restored on drop
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enable() -> Result<RawTerminal, String> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(String::from("--tui requires a terminal"));
+        }
+
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        // reads return at once, with whatever input is available
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(String::from("Unable to switch the terminal into raw mode"));
+        }
+
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        Ok(RawTerminal { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+// Screen `width` pixels wide as rows of half blocks, the foreground colour is the upper pixel
+// and the background the lower one
+pub fn render(persistence: &Persistence, palette: &Palette, width: usize) -> String {
+    let height = persistence.values.len() / width;
+    let shade = |index: usize| {
+        palette.background().mix(palette.color(persistence.values[index]), persistence.levels[index])
+    };
+
+    let mut text = String::new();
+    let mut colors: Option<(Rgb, Rgb)> = None;
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let upper = shade(y * width + x);
+            let lower = if y + 1 < height { shade((y + 1) * width + x) } else { palette.background() };
+            // colours are only sent when they change
+            if colors != Some((upper, lower)) {
+                text.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                    upper.r, upper.g, upper.b, lower.r, lower.g, lower.b
+                ));
+                colors = Some((upper, lower));
+            }
+            text.push(UPPER_HALF_BLOCK);
+        }
+        text.push_str("\x1b[0m\r\n");
+        colors = None;
+    }
+    text
+}
+
+fn status_line(status: &Status) -> String {
+    let state = match status {
+        Status::Running => String::from("running"),
+        Status::WaitingKey => String::from("waiting key..."),
+        Status::Paused => String::from("paused"),
+        Status::Halted(reason) => format!("halted: {reason}"),
+        Status::Fault(fault) => format!("fault: {fault}"),
+    };
+    format!("\x1b[2K{state}  (Esc - quit)")
+}
+
+pub struct TerminalVideo {
+    persistence: Persistence,
+    palette: Palette,
+    last_screen: String,
+    // Display size of the last frame, the terminal is cleared when it changes
+    display_size: (usize, usize),
+    // Latest debugger or analysis message, shown below the status line
+    last_message: Option<String>,
+}
+
+impl TerminalVideo {
+    pub fn new(options: &Options) -> TerminalVideo {
+        TerminalVideo {
+            persistence: Persistence::new(options.persistence, DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize),
+            palette: options.palette.clone(),
+            last_screen: String::new(),
+            display_size: (DISPLAY_WIDTH as usize, DISPLAY_HEIGHT as usize),
+            last_message: None,
+        }
+    }
+}
+
+impl VideoSink for TerminalVideo {
+    fn present(&mut self, computer: &Computer, status: &Status) -> Result<(), String> {
+        let display = &computer.display;
+        self.persistence.update(&display.memory);
+        let screen = render(&self.persistence, &self.palette, display.width());
+
+        // a resolution switch leaves rows of the previous screen behind
+        let mut frame = String::new();
+        if self.display_size != (display.width(), display.height()) {
+            self.display_size = (display.width(), display.height());
+            frame.push_str("\x1b[2J");
+        }
+
+        // unchanged screens aren't sent again, only the status line
+        frame.push_str("\x1b[H");
+        if screen != self.last_screen {
+            frame.push_str(&screen);
+        } else {
+            frame.push_str(&format!("\x1b[{}B", display.height().div_ceil(2)));
+        }
+        frame.push_str(&status_line(status));
+        if let Some(message) = &self.last_message {
+            frame.push_str(&format!("\r\n\x1b[2K{message}"));
+        }
+        self.last_screen = screen;
+
+        let mut stdout = std::io::stdout();
+        stdout.write_all(frame.as_bytes()).map_err(|e| e.to_string())?;
+        stdout.flush().map_err(|e| e.to_string())
+    }
+
+    // Printing would scroll the screen, messages replace each other instead
+    fn message(&mut self, text: &str) {
+        self.last_message = Some(text.to_string());
+    }
+}
+
+// Rings the terminal bell when the tone starts
+pub struct TerminalAudio {
+    tone: bool,
+}
+
+impl TerminalAudio {
+    pub fn new() -> TerminalAudio {
+        TerminalAudio { tone: false }
+    }
+}
+
+impl Default for TerminalAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSink for TerminalAudio {
+    fn set_tone(&mut self, on: bool) {
+        if on && !self.tone {
+            print!("\x07");
+        }
+        self.tone = on;
+    }
+}
+
+pub struct TerminalInput {
+    // Frames left until the keys are released
+    held_frames: [u32; 16],
+}
+
+impl TerminalInput {
+    pub fn new() -> TerminalInput {
+        TerminalInput { held_frames: [0; 16] }
+    }
+
+    // Turns bytes read from the terminal into events
+    pub fn parse(&mut self, input: &[u8]) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        for (key, frames) in self.held_frames.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    events.push(InputEvent::Key(key, false));
+                }
+            }
+        }
+
+        let mut bytes = input.iter().copied().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                CTRL_C => events.push(InputEvent::Quit),
+                // a lone escape is the Esc key, otherwise it starts a sequence (arrows, function keys)
+                ESCAPE => match bytes.next() {
+                    None => events.push(InputEvent::Quit),
+                    Some(_) => {
+                        // parameters up to the final byte, which is dropped too
+                        while bytes.next_if(|byte| !(0x40..=0x7E).contains(byte)).is_some() {}
+                        bytes.next();
+                    },
+                },
+                _ => {
+                    if let Some(key) = key_index(byte) {
+                        if self.held_frames[key] == 0 {
+                            events.push(InputEvent::Key(key, true));
+                        }
+                        self.held_frames[key] = KEY_HOLD_FRAMES;
+                    }
+                },
+            }
+        }
+        events
+    }
+}
+
+impl Default for TerminalInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, _frame: u64) -> Result<Vec<InputEvent>, String> {
+        let mut input = [0u8; 64];
+        let count = std::io::stdin().read(&mut input).map_err(|e| e.to_string())?;
+        Ok(self.parse(&input[..count]))
+    }
+}
+
+// Runs the ROM in the terminal until Esc or Ctrl-C, returns status of the last frame
+pub fn run(computer: &mut Computer, options: &Options) -> Result<Status, String> {
+    let terminal = RawTerminal::enable()?;
+
+    let mut runner = Runner::new(TerminalVideo::new(options), TerminalAudio::new(), TerminalInput::new());
+    runner.realtime = true;
+    runner.stop_on_halt = options.exit_on_halt;
+    let status = runner.run(computer);
+
+    drop(terminal);
+    if let Ok(Status::Halted(reason)) = &status {
+        println!("Halted: {reason}");
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::Mode;
+
+    #[test]
+    fn maps_keys_to_keypad() {
+        assert_eq!(key_index(b'1'), Some(0x1));
+        assert_eq!(key_index(b'V'), Some(0xF));
+        assert_eq!(key_index(b'x'), Some(0x0));
+        assert_eq!(key_index(b'p'), None);
+    }
+
+    #[test]
+    fn holds_keys_until_released() {
+        let mut input = TerminalInput::new();
+        assert_eq!(input.parse(b"w\x1b[A"), [InputEvent::Key(5, true)]);
+        for _ in 1..KEY_HOLD_FRAMES {
+            assert!(input.parse(b"").is_empty());
+        }
+        assert_eq!(input.parse(b""), [InputEvent::Key(5, false)]);
+        assert_eq!(input.parse(b"\x1b"), [InputEvent::Quit]);
+    }
+
+    #[test]
+    fn skips_whole_escape_sequences() {
+        let mut input = TerminalInput::new();
+        // arrows and function keys end with a letter or `~`, which must not press a key
+        assert!(input.parse(b"\x1b[A\x1b[D\x1bOP\x1b[15~").is_empty());
+        assert_eq!(input.parse(b"\x1b[Bq"), [InputEvent::Key(4, true)]);
+    }
+
+    #[test]
+    fn renders_two_rows_per_line() {
+        let mut screen = vec![0u8; DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize];
+        screen[DISPLAY_WIDTH as usize] = 1;
+        let mut persistence = Persistence::new(Mode::Off, screen.len());
+        persistence.update(&screen);
+
+        let text = render(&persistence, &Palette::default(), DISPLAY_WIDTH as usize);
+        let lines: Vec<&str> = text.split("\r\n").filter(|line| !line.is_empty()).collect();
+        assert_eq!(lines.len(), DISPLAY_HEIGHT as usize / 2);
+        assert!(lines[0].starts_with("\x1b[38;2;0;0;0m\x1b[48;2;255;255;255m\u{2580}\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m"));
+        assert_eq!(lines[1].matches(UPPER_HALF_BLOCK).count(), DISPLAY_WIDTH as usize);
+    }
+
+    #[test]
+    fn renders_any_display_size() {
+        let mut persistence = Persistence::new(Mode::Off, 0);
+        persistence.update(&[0u8; 128 * 64]);
+
+        let text = render(&persistence, &Palette::default(), 128);
+        let lines: Vec<&str> = text.split("\r\n").filter(|line| !line.is_empty()).collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0].matches(UPPER_HALF_BLOCK).count(), 128);
+    }
+}
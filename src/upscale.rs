@@ -0,0 +1,204 @@
+// Software upscalers applied to the coloured screen before it's uploaded for presentation
+
+use std::fmt;
+
+use crate::palette::Rgb;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    None,
+    // EPX: corners take the colour of matching neighbours, rounding diagonal edges
+    Scale2x,
+    Scale3x,
+    // Scale2x blending corners instead of replacing them, like hqx
+    Smooth,
+    // Darkened scanlines and an RGB aperture-grille mask
+    Crt,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 5] = [Filter::None, Filter::Scale2x, Filter::Scale3x, Filter::Smooth, Filter::Crt];
+
+    pub fn parse(text: &str) -> Result<Filter, String> {
+        match text {
+            "none" => Ok(Filter::None),
+            "scale2x" => Ok(Filter::Scale2x),
+            "scale3x" => Ok(Filter::Scale3x),
+            "smooth" => Ok(Filter::Smooth),
+            "crt" => Ok(Filter::Crt),
+            _ => Err(format!("Unknown filter: {text}, expected none, scale2x, scale3x, smooth or crt")),
+        }
+    }
+
+    // Output pixels per input pixel in each direction
+    pub fn factor(&self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x | Filter::Smooth => 2,
+            Filter::Scale3x | Filter::Crt => 3,
+        }
+    }
+
+    pub fn next(&self) -> Filter {
+        let index = Filter::ALL.iter().position(|filter| filter == self).unwrap_or(0);
+        Filter::ALL[(index + 1) % Filter::ALL.len()]
+    }
+
+    // Upscales a `width` x `height` image by `factor()`
+    pub fn apply(&self, image: &[Rgb], width: usize, height: usize) -> Vec<Rgb> {
+        match self {
+            Filter::None => image.to_vec(),
+            Filter::Scale2x => scale2x(image, width, height, |_, neighbour| neighbour),
+            Filter::Smooth => scale2x(image, width, height, |center, neighbour| center.mix(neighbour, 128)),
+            Filter::Scale3x => scale3x(image, width, height),
+            Filter::Crt => crt(image, width, height),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Filter::None => "none",
+            Filter::Scale2x => "scale2x",
+            Filter::Scale3x => "scale3x",
+            Filter::Smooth => "smooth",
+            Filter::Crt => "crt",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// Pixel at the offset from (x, y), edges are repeated
+fn neighbour(image: &[Rgb], width: usize, height: usize, x: usize, y: usize, dx: isize, dy: isize) -> Rgb {
+    let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+    let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+    image[y * width + x]
+}
+
+// Scale2x, `corner` gives the colour of a corner which lies on an edge
+fn scale2x(image: &[Rgb], width: usize, height: usize, corner: impl Fn(Rgb, Rgb) -> Rgb) -> Vec<Rgb> {
+    let mut output = vec![Rgb::new(0); image.len() * 4];
+    let output_width = width * 2;
+
+    for y in 0..height {
+        for x in 0..width {
+            let at = |dx, dy| neighbour(image, width, height, x, y, dx, dy);
+            let (center, up, left, right, down) = (at(0, 0), at(0, -1), at(-1, 0), at(1, 0), at(0, 1));
+
+            let mut block = [center; 4];
+            if up != down && left != right {
+                if left == up {
+                    block[0] = corner(center, left);
+                }
+                if up == right {
+                    block[1] = corner(center, right);
+                }
+                if left == down {
+                    block[2] = corner(center, left);
+                }
+                if down == right {
+                    block[3] = corner(center, right);
+                }
+            }
+
+            for (index, color) in block.iter().enumerate() {
+                output[(y * 2 + index / 2) * output_width + x * 2 + index % 2] = *color;
+            }
+        }
+    }
+    output
+}
+
+fn scale3x(image: &[Rgb], width: usize, height: usize) -> Vec<Rgb> {
+    let mut output = vec![Rgb::new(0); image.len() * 9];
+    let output_width = width * 3;
+
+    for y in 0..height {
+        for x in 0..width {
+            let at = |dx, dy| neighbour(image, width, height, x, y, dx, dy);
+            let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+            let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+            let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+            let mut block = [e; 9];
+            if b != h && d != f {
+                block[0] = if d == b { d } else { e };
+                block[1] = if (d == b && e != c) || (b == f && e != a) { b } else { e };
+                block[2] = if b == f { f } else { e };
+                block[3] = if (d == b && e != g) || (d == h && e != a) { d } else { e };
+                block[5] = if (b == f && e != i) || (h == f && e != c) { f } else { e };
+                block[6] = if d == h { d } else { e };
+                block[7] = if (d == h && e != i) || (h == f && e != g) { h } else { e };
+                block[8] = if h == f { f } else { e };
+            }
+
+            for (index, color) in block.iter().enumerate() {
+                output[(y * 3 + index / 3) * output_width + x * 3 + index % 3] = *color;
+            }
+        }
+    }
+    output
+}
+
+fn crt(image: &[Rgb], width: usize, height: usize) -> Vec<Rgb> {
+    let mut output = vec![Rgb::new(0); image.len() * 9];
+    let output_width = width * 3;
+    let black = Rgb::new(0);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = image[y * width + x];
+            // each column lets one channel through fully, like the phosphor stripes
+            let columns = [
+                Rgb { r: color.r, ..color.mix(black, 80) },
+                Rgb { g: color.g, ..color.mix(black, 80) },
+                Rgb { b: color.b, ..color.mix(black, 80) },
+            ];
+
+            for row in 0..3 {
+                for (column, stripe) in columns.iter().enumerate() {
+                    // gap between scanlines
+                    let shade = if row == 2 { stripe.mix(black, 128) } else { *stripe };
+                    output[(y * 3 + row) * output_width + x * 3 + column] = shade;
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const O: Rgb = Rgb::new(0x000000);
+    const X: Rgb = Rgb::new(0xFFFFFF);
+
+    #[test]
+    fn scale2x_rounds_corners() {
+        // inner corner of an L shape gets filled diagonally
+        let image = [X, O, X, X];
+        let output = Filter::Scale2x.apply(&image, 2, 2);
+        #[rustfmt::skip]
+        let expected = [
+            X, X, O, O,
+            X, X, X, O,
+            X, X, X, X,
+            X, X, X, X,
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn filters_keep_flat_areas() {
+        for filter in Filter::ALL {
+            let factor = filter.factor();
+            let output = filter.apply(&[O; 6], 3, 2);
+            assert_eq!(output.len(), 6 * factor * factor, "{filter}");
+            if filter != Filter::Crt {
+                assert!(output.iter().all(|color| *color == O), "{filter}");
+            }
+        }
+    }
+}
@@ -1,8 +1,15 @@
+pub mod png;
+
 use std::path::Path;
 use std::fs;
 use std::io::{Error, ErrorKind};
 
+// Loads ROM by path, or by name from the roms directory, e.g. `ibm` for `./roms/ibm.ch8`
 pub fn load_rom(rom_name: &str) -> Result<Vec<u8>, Error> {
+    if Path::new(rom_name).is_file() {
+        return fs::read(rom_name);
+    }
+
     let filepath = format!("./roms/{}.ch8", rom_name);
     let path = Path::new(filepath.as_str());
 
@@ -0,0 +1,231 @@
+// Community test-suite ROMs (https://github.com/Timendus/chip8-test-suite) run headlessly
+// under every platform profile. The suite is GPL licensed and isn't distributed with CRAB8,
+// its ROMs go to `tests/roms` (CI downloads them), tests of missing ROMs are skipped.
+// Result glyphs are `tests/suite/glyphs/ok.txt` and `err.txt` in `Display::to_ascii` format.
+
+use std::path::{Path, PathBuf};
+
+use crab8::computer::{Computer, Status};
+use crab8::computer::display::Display;
+use crab8::computer::instruction::{encode, Instruction};
+use crab8::computer::quirks::Platform;
+use crab8::headless::{run_frames, KeyScript};
+use crab8::utils;
+
+const FRAMES: u64 = 600;
+// Menus are shown right away, the choice is pressed after they've been drawn
+const MENU_FRAME: u64 = 30;
+
+// Pattern of lit and unlit pixels looked up on the screen
+pub struct Glyph {
+    rows: Vec<Vec<bool>>,
+}
+
+impl Glyph {
+    pub fn parse(text: &str) -> Glyph {
+        let rows = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim().chars().map(|pixel| pixel == '#').collect())
+            .collect();
+        Glyph { rows }
+    }
+
+    pub fn load(path: &Path) -> Glyph {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("result glyph {} is missing: {e}", path.display()));
+        Glyph::parse(&text)
+    }
+
+    // Number of places where the screen matches the glyph exactly
+    pub fn count(&self, display: &Display) -> usize {
+        let height = self.rows.len();
+        let width = self.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let (screen, screen_width, screen_height) = (&display.memory, display.width(), display.height());
+        if height == 0 || width > screen_width || height > screen_height {
+            return 0;
+        }
+
+        let mut count = 0;
+        for y in 0..=screen_height - height {
+            for x in 0..=screen_width - width {
+                let matches = self.rows.iter().enumerate().all(|(row_index, row)| {
+                    row.iter().enumerate().all(|(column, lit)| {
+                        (screen[(y + row_index) * screen_width + x + column] != 0) == *lit
+                    })
+                });
+                if matches {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+fn suite_dir(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+// Key choosing the platform in the quirks test menu
+fn platform_key(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 1,
+        Platform::Schip => 2,
+        Platform::XoChip => 3,
+    }
+}
+
+// Menu keys of the quirks test, SUPER-CHIP asks for modern or legacy behaviour next
+fn quirks_keys(platform: Platform) -> String {
+    let keys = format!("{MENU_FRAME} {} press", platform_key(platform));
+    match platform {
+        Platform::Schip => format!("{keys},{} 1 press", MENU_FRAME * 2),
+        _ => keys,
+    }
+}
+
+// ROM bytes, None when the ROM isn't in tests/roms
+fn suite_rom(file: &str) -> Option<Vec<u8>> {
+    let rom_path = suite_dir("tests/roms").join(file);
+    if !rom_path.is_file() {
+        eprintln!("skipped: test-suite ROM {file} is missing from tests/roms");
+        return None;
+    }
+    Some(utils::load_rom(&rom_path.to_string_lossy()).unwrap())
+}
+
+// Runs the ROM with the key script and returns the final screen
+fn run_rom(file: &str, rom_data: Vec<u8>, platform: Platform, keys: &str) -> Computer {
+    let mut computer = Computer::new();
+    computer.reset();
+    computer.load_rom(rom_data).unwrap();
+    computer.cpu.quirks = platform.quirks();
+
+    let script = KeyScript::parse(keys).unwrap();
+    let (_, status) = run_frames(&mut computer, &script, Some(FRAMES));
+    assert!(!matches!(status, Status::Fault(_)), "{file} on {platform}: {status:?}");
+
+    computer
+}
+
+// Numbers of passed and failed results shown on the screen
+fn results(display: &Display) -> (usize, usize) {
+    let glyphs_dir = suite_dir("tests/suite/glyphs");
+    let (ok, err) = (Glyph::load(&glyphs_dir.join("ok.txt")), Glyph::load(&glyphs_dir.join("err.txt")));
+    (ok.count(display), err.count(display))
+}
+
+// Checks the screen shows only passing results, skipped when the ROM is missing
+fn check_results(file: &str, platform: Platform, keys: &str) {
+    let rom_data = match suite_rom(file) {
+        Some(rom_data) => rom_data,
+        None => return,
+    };
+
+    let computer = run_rom(file, rom_data, platform, keys);
+    let (passed, failed) = results(&computer.display);
+    assert!(
+        failed == 0 && passed > 0,
+        "{file} on {platform}: {passed} passed, {failed} failed\n{}",
+        computer.display.to_ascii()
+    );
+}
+
+// ROM drawing the glyph from `file` at the given positions, the sprite is at 0x300
+fn glyph_rom(file: &str, positions: &[(u8, u8)]) -> Vec<u8> {
+    let glyph = Glyph::load(&suite_dir("tests/suite/glyphs").join(file));
+    let mut program = Vec::new();
+    for (x, y) in positions.iter() {
+        program.extend([
+            Instruction::LdI(0x300),
+            Instruction::LdByte(0, *x),
+            Instruction::LdByte(1, *y),
+            Instruction::Drw(0, 1, glyph.rows.len() as u8),
+        ]);
+    }
+    program.push(Instruction::Jp(0x200 + program.len() as u16 * 2));
+
+    let mut rom: Vec<u8> = program.iter().flat_map(|instruction| encode(instruction).value().to_be_bytes()).collect();
+    rom.resize(0x100, 0);
+    // rows are at most 8 pixels wide
+    for row in glyph.rows.iter() {
+        rom.push(row.iter().fold(0, |byte, lit| byte << 1 | *lit as u8) << (8 - row.len()));
+    }
+    rom
+}
+
+#[test]
+fn ibm_logo() {
+    let rom_data = match suite_rom("2-ibm-logo.ch8") {
+        Some(rom_data) => rom_data,
+        None => return,
+    };
+    for platform in Platform::ALL {
+        let computer = run_rom("2-ibm-logo.ch8", rom_data.clone(), platform, "");
+        assert!(computer.halted.is_some(), "IBM logo on {platform} didn't finish");
+        assert!(computer.display.memory.iter().any(|pixel| *pixel != 0));
+    }
+}
+
+#[test]
+fn corax_opcodes() {
+    for platform in Platform::ALL {
+        check_results("3-corax+.ch8", platform, "");
+    }
+}
+
+#[test]
+fn flags() {
+    for platform in Platform::ALL {
+        check_results("4-flags.ch8", platform, "");
+    }
+}
+
+#[test]
+fn quirks() {
+    // the menu asks for the platform to test
+    for platform in Platform::ALL {
+        check_results("5-quirks.ch8", platform, &quirks_keys(platform));
+    }
+}
+
+#[test]
+fn keypad_get_key() {
+    // menu entry 3 is the Fx0A test, which then waits for a key to be pressed and released
+    let keys = format!("{MENU_FRAME} 3 press,{} 5 down,{} 5 up", MENU_FRAME + 30, MENU_FRAME + 40);
+    for platform in Platform::ALL {
+        check_results("6-keypad.ch8", platform, &keys);
+    }
+}
+
+#[test]
+fn results_are_read_from_the_screen() {
+    let computer = run_rom("ok", glyph_rom("ok.txt", &[(8, 8), (24, 8), (8, 20)]), Platform::Chip8, "");
+    assert_eq!(results(&computer.display), (3, 0));
+
+    let computer = run_rom("err", glyph_rom("err.txt", &[(40, 16)]), Platform::Chip8, "");
+    assert_eq!(results(&computer.display), (0, 1));
+}
+
+#[test]
+fn quirks_menu_picks_modern_schip() {
+    assert_eq!(quirks_keys(Platform::Chip8), "30 1 press");
+    assert_eq!(quirks_keys(Platform::Schip), "30 2 press,60 1 press");
+    KeyScript::parse(&quirks_keys(Platform::Schip)).unwrap();
+}
+
+#[test]
+fn glyph_count_finds_exact_matches() {
+    let glyph = Glyph::parse("#.\n.#\n");
+    let mut display = Display::new();
+    let width = display.width();
+    display.memory[0] = 1;
+    display.memory[width + 1] = 1;
+    assert_eq!(glyph.count(&display), 1);
+
+    display.memory[width * 10 + 20] = 1;
+    display.memory[width * 11 + 21] = 1;
+    assert_eq!(glyph.count(&display), 2);
+    assert_eq!(Glyph::parse("").count(&display), 0);
+}